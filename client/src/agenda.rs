@@ -0,0 +1,211 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::Path,
+    process,
+    sync::mpsc::RecvTimeoutError,
+    thread,
+    time::Duration,
+};
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    app::Config,
+    backend::{AppStatus, AuthError, Backend},
+    ics,
+    outlook::{CalendarEvent, EventCommand},
+    provider::Provider,
+    ui::{format_span, to_display},
+    CONFIG,
+};
+
+#[derive(Serialize)]
+struct AgendaEntry<'a> {
+    id: &'a str,
+    subject: &'a str,
+    // RFC 3339 in the display timezone.
+    start: String,
+    end: String,
+    all_day: bool,
+    location: &'a str,
+    organizer: &'a str,
+    url: Option<&'a str>,
+}
+
+impl<'a> From<&'a CalendarEvent> for AgendaEntry<'a> {
+    fn from(event: &'a CalendarEvent) -> Self {
+        Self {
+            id: &event.id,
+            subject: &event.subject,
+            start: to_display(event.start_time).to_rfc3339(),
+            end: to_display(event.end_time).to_rfc3339(),
+            all_day: event.is_all_day,
+            location: &event.location,
+            organizer: &event.organizer,
+            url: event.meeting_url(),
+        }
+    }
+}
+
+// Prints the upcoming events once, for status bars and scripts.
+pub fn run(json: bool) -> io::Result<()> {
+    let events = fetch_events();
+
+    let mut stdout = io::stdout().lock();
+    if json {
+        let entries = events.iter().map(AgendaEntry::from).collect::<Vec<_>>();
+        serde_json::to_writer_pretty(&mut stdout, &entries)?;
+        writeln!(stdout)?;
+    } else {
+        for event in &events {
+            match event.location.is_empty() {
+                true => writeln!(stdout, "{}  {}", format_span(event), event.subject)?,
+                false => writeln!(
+                    stdout,
+                    "{}  {} ({})",
+                    format_span(event),
+                    event.subject,
+                    event.location
+                )?,
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn export(path: &Path) -> io::Result<()> {
+    fs::write(path, ics::write_calendar(&fetch_events()))
+}
+
+// Streams changes as they are polled, one JSON object per line:
+// {"change": "add" | "update", "event": {...}} or {"change": "remove", "id": "..."}.
+// Runs until stdout is closed.
+pub fn watch() -> io::Result<()> {
+    let backend = Backend::new();
+    sign_in(&backend);
+
+    // Every poll resends the whole window, so only differences from the last entry are printed.
+    let mut last = HashMap::<String, Value>::new();
+    let mut stdout = io::stdout().lock();
+    let result = loop {
+        // Problems go to stderr, keeping stdout parseable.
+        for status in backend.status_rx.try_iter() {
+            if let AppStatus::Error(error) = status {
+                eprintln!("ERROR: {error}");
+            }
+        }
+        for error in backend.error_rx.try_iter() {
+            eprintln!("ERROR: {error}");
+        }
+
+        let command = match backend.event_rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(command) => command,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break Ok(()),
+        };
+        let line = match command {
+            EventCommand::Add(event) => {
+                let entry = json!(AgendaEntry::from(&*event));
+                let change = match last.insert(event.id.clone(), entry.clone()) {
+                    None => "add",
+                    Some(previous) if previous == entry => continue,
+                    Some(_) => "update",
+                };
+                json!({ "change": change, "event": entry })
+            }
+            EventCommand::Remove(id) => match last.remove(&id) {
+                Some(_) => json!({ "change": "remove", "id": id }),
+                None => continue,
+            },
+        };
+
+        match writeln!(stdout, "{line}").and_then(|_| stdout.flush()) {
+            Ok(()) => (),
+            // The reader has gone, e.g. a status bar restarting. Rust ignores SIGPIPE, so this
+            // arrives as an error rather than killing the process.
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => break Ok(()),
+            Err(error) => break Err(error),
+        }
+    };
+    backend.shutdown();
+    result
+}
+
+// Fetches the window once through the same providers the TUI polls.
+fn fetch_events() -> Vec<CalendarEvent> {
+    let config = CONFIG.get().unwrap();
+    let backend = Backend::new();
+    sign_in(&backend);
+
+    // Each refresh loop sends its events before reporting Ready, so once every loop has reported
+    // the channel holds the whole window.
+    let mut pending = feeds(config);
+    while pending > 0 {
+        match backend
+            .status_rx
+            .recv_timeout(Duration::from_millis(config.auth_timeout_millis))
+        {
+            Ok(AppStatus::Ready) => pending -= 1,
+            Ok(AppStatus::Error(error)) => fail(&error),
+            Ok(_) => (),
+            Err(RecvTimeoutError::Timeout) => fail("Timed out fetching events"),
+            Err(RecvTimeoutError::Disconnected) => fail("The refresh thread stopped unexpectedly"),
+        }
+    }
+
+    let mut events = backend
+        .event_rx
+        .try_iter()
+        .filter_map(|command| match command {
+            EventCommand::Add(event) => Some(*event),
+            EventCommand::Remove(_) => None,
+        })
+        .collect::<Vec<_>>();
+    events.sort_by_key(|event| event.start_time);
+    backend.shutdown();
+    events
+}
+
+// Both sign-in flows complete elsewhere, so poll until one does.
+fn sign_in(backend: &Backend) {
+    let mut shown = None;
+    loop {
+        match backend.start() {
+            Ok(()) => return,
+            Err(AuthError::Browser | AuthError::RequestingDeviceCode) => {
+                thread::sleep(Duration::from_millis(500))
+            }
+            Err(AuthError::DeviceCode(device_code)) => {
+                if shown.as_ref() != Some(&device_code.user_code) {
+                    eprintln!("{}", AuthError::DeviceCode(device_code.clone()));
+                    shown = Some(device_code.user_code);
+                }
+                thread::sleep(Duration::from_secs(5));
+            }
+            Err(error) => fail(&error.to_string()),
+        }
+    }
+}
+
+// Refresh loops that will report in, one per polled calendar of each account plus one for the
+// ICS feeds.
+fn feeds(config: &Config) -> usize {
+    let account = match config.provider {
+        Provider::Outlook => config
+            .outlook_accounts()
+            .iter()
+            .map(|account| account.outlook.calendar_ids.len().max(1))
+            .sum(),
+        Provider::Google | Provider::CalDav | Provider::Mock => 1,
+        Provider::Ics => 0,
+    };
+    account + usize::from(!config.ics_urls.is_empty())
+}
+
+fn fail(error: &str) -> ! {
+    eprintln!("ERROR: {error}");
+    process::exit(1);
+}
@@ -1,92 +1,329 @@
 use crate::{
-    backend::Backend as AppBackend,
-    outlook::CalendarEvent,
-    ui::{render_popup, render_selection, render_table, TableColors, PALETTES},
+    auth,
+    backend::{http_client, AppStatus, AuthError, Backend as AppBackend},
+    ics,
+    keybindings::{Action, Keybindings},
+    outlook::{self, respond, CalendarEvent, EventCommand, EventResponse, ShowAs},
+    provider::{CalendarProvider, Provider},
+    state::State,
+    ui::{
+        format_time, render_confirm_quit, render_error, render_free_busy, render_help,
+        render_popup, render_selection, render_table, render_too_small, render_week, to_display,
+        AlertTemplate, CategoryColors, SelectionStyle, TableColors, TableLayout, Theme, PALETTES,
+    },
     CONFIG, CONFIG_PATH,
 };
-use chrono::{DateTime, Utc};
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use arboard::Clipboard;
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    style::Colored,
+};
+use notify_rust::Notification;
 use ratatui::{backend::Backend, widgets::TableState, Frame, Terminal};
+use rodio::{Decoder, OutputStream, Sink};
 use serde::Deserialize;
-use std::{collections::BTreeMap, process::Command, time::Duration};
+use std::{
+    cell::OnceCell,
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    fs::{self, File},
+    io::{BufReader, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+    time::{Duration, Instant},
+};
 use tokio::{io, time::sleep};
+use tracing_subscriber::filter::LevelFilter;
 
 #[derive(Clone, Copy)]
 pub enum Focus {
     Table,
     Selected,
     Popup,
+    Error,
+    Week,
+    // Today's meetings as a single bar, for spotting open slots.
+    FreeBusy,
+    Help,
+    ConfirmQuit,
+}
+
+// What the table shows, in order: see App::listing.
+struct Listing {
+    ids: Vec<String>,
+    outside_hours: usize,
+    layout: TableLayout,
 }
 
 pub struct App {
+    // Indexes visible events; day header rows are laid out around them by TableLayout.
     pub table_state: TableState,
+    // Rows of the table drawn last frame; scrolling carries on from its start.
+    pub table_rows: Range<usize>,
     pub focus: Focus,
-    pub events: BTreeMap<DateTime<Utc>, CalendarEvent>,
+    // Keyed by ID; views sort by start themselves, as meetings can share a start time.
+    pub events: HashMap<String, CalendarEvent>,
+    // The table's events in order, worked out on first use and dropped by relist when events,
+    // the filter, the account shown or working hours change, rather than sorted every frame.
+    listing: OnceCell<Listing>,
+    // Per listed event, the copies of it read through other calendars (see add_event).
+    pub copies: HashMap<String, Vec<CalendarEvent>>,
     pub colors: TableColors,
+    // Index into PALETTES of the colors in use; None for a custom [theme].
+    pub palette: Option<usize>,
     pub backend: AppBackend,
+    pub auth_error: Option<AuthError>,
+    pub status: Option<String>,
+    pub filter: String,
+    pub filtering: bool,
+    pub alert_id: Option<String>,
+    pub alert_offset: Option<i64>,
+    // Weeks ahead of the current one shown in the week view.
+    pub week_offset: i64,
+    // Lines scrolled in the detail pane; clamped to the content when rendered.
+    pub detail_scroll: u16,
+    // Full events by ID, loaded when opened while [outlook] select trims what polls return.
+    pub details: HashMap<String, CalendarEvent>,
+    // Show the detail pane's body as the HTML it was converted from, for debugging.
+    pub show_raw_body: bool,
+    // View to go back to when the help or quit overlay closes.
+    pub overlay_return: Focus,
+    pub app_status: AppStatus,
+    // Meeting about to be joined automatically, with when its link opens.
+    pub pending_join: Option<(String, DateTime<Utc>)>,
+    // Events already auto-joined, so a rescheduled timer doesn't open them twice.
+    pub joined: HashSet<String>,
+    // When the alert popup closes by itself, if popup_auto_dismiss_seconds is set.
+    pub popup_until: Option<DateTime<Utc>>,
+    // Shown in the popup instead of an alert when a meeting was moved between polls.
+    pub change_notice: Option<String>,
+    // Only events from this [[accounts]] entry are shown when set.
+    pub account_filter: Option<String>,
+    // Collapse events outside [working_hours] into a summary row, rather than dimming them.
+    pub hide_outside_hours: bool,
+    // Letters typed in the table that aren't bound to anything, with when the last one came in.
+    pub type_ahead: String,
+    pub type_ahead_at: Instant,
 }
 
+// Pause after which typing starts a new prefix.
+const TYPE_AHEAD_TIMEOUT: Duration = Duration::from_secs(1);
+
 impl App {
     pub fn new(backend: AppBackend) -> Self {
-        backend.start();
+        let config = CONFIG.get().unwrap();
+        let state = State::load();
+        // Only the lone [outlook] account switches calendars, and the config may have changed
+        // since.
+        if config.accounts.is_empty() && config.outlook.calendar_ids.is_empty() {
+            if let Some(calendar_id) = state.calendar_id {
+                *backend.calendar_id.write().unwrap() = Some(calendar_id);
+            }
+        }
+        let auth_error = backend.start().err();
+        // Drops every color escape, including the fixed ones in popups, leaving only attributes.
+        if config.no_color() {
+            Colored::set_ansi_color_disabled(true);
+        }
+        let palette = match state.theme {
+            Some(index) if index < PALETTES.len() && state.configured_theme == config.palette() => {
+                Some(index)
+            }
+            _ => config.palette(),
+        };
         Self {
-            events: BTreeMap::new(),
-            colors: TableColors::new(&PALETTES[CONFIG.get().unwrap().theme]),
+            events: HashMap::new(),
+            listing: OnceCell::new(),
+            copies: HashMap::new(),
+            colors: match (config.no_color(), palette) {
+                (true, _) => TableColors::monochrome(),
+                (false, Some(index)) => TableColors::new(&PALETTES[index]),
+                (false, None) => TableColors::from(&config.theme),
+            },
+            palette,
             table_state: TableState::default().with_selected(0),
-            focus: Focus::Table,
+            table_rows: 0..0,
+            focus: match auth_error {
+                Some(_) => Focus::Error,
+                None => state.view.focus(),
+            },
             backend,
+            auth_error,
+            status: None,
+            filter: state.filter,
+            filtering: false,
+            alert_id: None,
+            alert_offset: None,
+            week_offset: 0,
+            detail_scroll: 0,
+            details: HashMap::new(),
+            show_raw_body: false,
+            overlay_return: Focus::Table,
+            app_status: AppStatus::Authenticating,
+            pending_join: None,
+            joined: HashSet::new(),
+            popup_until: None,
+            change_notice: None,
+            account_filter: state
+                .account_filter
+                .filter(|name| config.accounts.iter().any(|account| account.name == *name)),
+            hide_outside_hours: state.hide_outside_hours && config.working_hours.is_some(),
+            type_ahead: String::new(),
+            type_ahead_at: Instant::now(),
         }
     }
 
     pub fn run<B: Backend>(mut self, terminal: &mut Terminal<B>) -> io::Result<()> {
+        self.backend.listen_for_signals();
+        let result = self.event_loop(terminal);
+        self.save_state();
+        self.backend.shutdown();
+        result
+    }
+
+    fn save_state(&self) {
+        // A --demo session's filter and view aren't the user's, so they're left as they were.
+        if matches!(CONFIG.get().unwrap().provider, Provider::Mock) {
+            return;
+        }
+        // Overlays count as the view they were opened over.
+        let focus = match self.focus {
+            Focus::Help | Focus::ConfirmQuit => self.overlay_return,
+            focus => focus,
+        };
+        State {
+            view: focus.into(),
+            filter: self.filter.clone(),
+            calendar_id: self.backend.calendar_id.read().unwrap().clone(),
+            account_filter: self.account_filter.clone(),
+            hide_outside_hours: self.hide_outside_hours,
+            theme: self.palette,
+            configured_theme: CONFIG.get().unwrap().palette(),
+        }
+        .save();
+    }
+
+    fn event_loop<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> io::Result<()> {
         loop {
             terminal.draw(|f| self.ui(f))?;
 
             // Manual event handlers.
             if let Ok(true) = event::poll(Duration::from_millis(50)) {
                 if let Event::Key(key) = event::read()? {
-                    if key.kind == KeyEventKind::Press {
-                        match key.code {
-                            KeyCode::Char('q') => return Ok(()),
-                            KeyCode::Char('h') => self.set_focus(Focus::Table),
-                            KeyCode::Char('l') => self.set_focus(Focus::Selected),
-                            KeyCode::Char('j') | KeyCode::Down => {
-                                if let Focus::Table = self.focus {
-                                    self.next()
-                                }
-                            }
-                            KeyCode::Char('k') | KeyCode::Up => {
-                                if let Focus::Table = self.focus {
-                                    self.previous()
-                                }
-                            }
-                            _ => (),
-                        }
+                    if key.kind == KeyEventKind::Press && self.handle_key(key) {
+                        return Ok(());
                     }
                 }
             }
+            // Exits like quitting, so the state is saved and the terminal restored.
+            if self.backend.quit_rx.try_recv().is_ok() {
+                return Ok(());
+            }
+
+            // Refreshes and expiry shift rows around, so remember the event rather than the row.
+            let selected = self
+                .selected_event()
+                .map(|event| (event.id.clone(), event.start_time));
 
             // Listen for new events from refresh thread.
-            while let Some(event) = self.poll_calendar_events() {
-                if let Some(time) = self.add_event(event) {
-                    self.spawn_timer(time);
+            while let Some(command) = self.poll_calendar_events() {
+                match command {
+                    EventCommand::Add(event) => {
+                        if let Some(id) = self.add_event(*event) {
+                            self.spawn_timer(&id);
+                        }
+                    }
+                    EventCommand::Remove(id) => self.remove_event(&id),
                 }
             }
 
+            // Startup and refresh progress; only the latest state matters.
+            if let Some(app_status) = self.backend.status_rx.try_iter().last() {
+                self.app_status = app_status;
+            }
+
+            // Sign-in completes in the browser or on another device, so keep checking on it.
+            if let Some(
+                AuthError::Browser | AuthError::RequestingDeviceCode | AuthError::DeviceCode(_),
+            ) = self.auth_error
+            {
+                self.retry_auth();
+            }
+
+            // Malformed events and the like; the refresh carries on regardless.
+            while let Ok(error) = self.backend.error_rx.try_recv() {
+                self.status = Some(error.to_string());
+            }
+
+            // Results of accept/decline requests.
+            while let Ok(result) = self.backend.action_rx.try_recv() {
+                self.status = Some(result.unwrap_or_else(|error| error));
+            }
+
+            // Events opened while polls only fetch a few fields.
+            while let Ok(event) = self.backend.detail_rx.try_recv() {
+                self.details.insert(event.id.clone(), event);
+            }
+
             // A timeout notification has been received, meaning an alert should be displayed.
-            if self.poll_timers() {
-                self.popup();
+            // Events removed or rescheduled since the timer was set no longer alert.
+            if let Some((id, start_time, offset)) = self.poll_timers() {
+                if self.is_scheduled(&id, start_time) {
+                    self.change_notice = None;
+                    self.alert_id = Some(id);
+                    self.alert_offset = Some(offset);
+                    self.popup();
+                    self.send_desktop_notification();
+                    self.play_alert_sound();
+                }
+            }
+
+            // Events starting now, for auto_join.
+            while let Ok((id, start_time)) = self.backend.join_rx.try_recv() {
+                self.queue_join(id, start_time);
+            }
+            self.tick_join();
+
+            // Nobody at the keyboard; overlays on top of the popup hold it open until closed.
+            if matches!(self.focus, Focus::Popup)
+                && self.popup_until.is_some_and(|until| until <= Utc::now())
+            {
+                self.dismiss();
             }
 
             // Clear expired events
+            let count = self.events.len();
             self.events.retain(|_, event| event.end_time >= Utc::now());
+            if self.events.len() != count {
+                self.relist();
+            }
+            self.copies.retain(|id, _| self.events.contains_key(id));
+
+            self.reselect(selected);
         }
     }
 
     pub fn ui(&mut self, frame: &mut Frame) {
         let area = frame.size();
 
-        match self.focus {
+        // Layout splits below this size overlap or collapse to nothing.
+        let config = CONFIG.get().unwrap();
+        if area.width < config.min_width || area.height < config.min_height {
+            render_too_small(frame, area);
+            return;
+        }
+
+        // Overlays are drawn over whichever view they were opened from.
+        let view = match self.focus {
+            Focus::Help | Focus::ConfirmQuit => self.overlay_return,
+            focus => focus,
+        };
+        match view {
             // Alert for upcoming event
             Focus::Popup => {
                 render_popup(self, frame, area);
@@ -99,57 +336,869 @@ impl App {
             Focus::Table => {
                 render_table(self, frame, area);
             }
+            // Authentication failure
+            Focus::Error => {
+                render_error(self, frame, area);
+            }
+            // Seven day columns
+            Focus::Week => {
+                render_week(self, frame, area);
+            }
+            // Busy and free time across today
+            Focus::FreeBusy => {
+                render_free_busy(self, frame, area);
+            }
+            Focus::Help | Focus::ConfirmQuit => (),
+        }
+        match self.focus {
+            Focus::Help => render_help(self, frame, area),
+            Focus::ConfirmQuit => render_confirm_quit(self, frame, area),
+            _ => (),
         }
     }
-    pub fn add_event(&mut self, event: CalendarEvent) -> Option<DateTime<Utc>> {
-        let start_time = event.start_time;
-        if self.events.insert(start_time, event).is_none() {
-            return Some(start_time);
+
+    // True when the app should quit.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        // Raw mode swallows SIGINT, so treat Ctrl-C as quit from anywhere.
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return true;
+        }
+        if self.filtering {
+            self.filter_input(key.code);
+            return false;
+        }
+        // Anything but y keeps the app open.
+        if let Focus::ConfirmQuit = self.focus {
+            if let KeyCode::Char('y' | 'Y') = key.code {
+                return true;
+            }
+            self.set_focus(self.overlay_return);
+            return false;
+        }
+
+        // Letters jump to matching subjects when they do nothing else here, and keep doing so
+        // while a prefix is being typed, so "st" reaches "Standup" rather than snoozing and
+        // jumping to now.
+        let type_ahead = match (self.focus, key.code) {
+            (Focus::Table, KeyCode::Char(c))
+                if !key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                Some(c)
+            }
+            _ => None,
+        };
+        if let Some(c) = type_ahead.filter(|_| self.typing_ahead()) {
+            self.type_ahead(c);
+            return false;
+        }
+
+        let mut handled = false;
+        for action in CONFIG.get().unwrap().keybindings.actions(key.code) {
+            if let Action::Quit = action {
+                if !CONFIG.get().unwrap().confirm_quit {
+                    return true;
+                }
+                // Replaces the help overlay rather than stacking on it.
+                if !matches!(self.focus, Focus::Help) {
+                    self.overlay_return = self.focus;
+                }
+                self.set_focus(Focus::ConfirmQuit);
+                return false;
+            }
+            handled |= self.handle_action(action);
+        }
+        if let Some(c) = type_ahead.filter(|_| !handled) {
+            self.type_ahead(c);
+        }
+        false
+    }
+
+    // False when the action does nothing in the current view.
+    pub fn handle_action(&mut self, action: Action) -> bool {
+        match (action, self.focus) {
+            (Action::Dismiss | Action::ClearFilter, _) if self.pending_join.is_some() => {
+                self.pending_join = None;
+                self.status = Some("Auto-join cancelled".to_string());
+            }
+            (Action::Retry, Focus::Error) => self.retry_auth(),
+            // Nothing but retry and quit applies until authenticated.
+            (_, Focus::Error) => (),
+            (Action::Help | Action::Dismiss, Focus::Help) => self.set_focus(self.overlay_return),
+            (_, Focus::Help) => (),
+            (Action::Help, focus) => {
+                self.overlay_return = focus;
+                self.set_focus(Focus::Help);
+            }
+            // h/l page through weeks here rather than switching views.
+            (Action::FocusTable | Action::FocusSelected, Focus::Week) => (),
+            (Action::PrevWeek, Focus::Week) => self.week_offset -= 1,
+            (Action::NextWeek, Focus::Week) => self.week_offset += 1,
+            (Action::WeekView, Focus::Week) => self.set_focus(Focus::Table),
+            (Action::WeekView, Focus::Table) => {
+                self.week_offset = 0;
+                self.set_focus(Focus::Week);
+            }
+            (Action::FreeBusy | Action::Dismiss, Focus::FreeBusy) => self.set_focus(Focus::Table),
+            (Action::FreeBusy, Focus::Table) => self.set_focus(Focus::FreeBusy),
+            (Action::FocusTable, _) => self.set_focus(Focus::Table),
+            (Action::FocusSelected, Focus::Table) => {
+                self.set_focus(Focus::Selected);
+                self.fetch_details();
+            }
+            (Action::FocusSelected, _) => self.set_focus(Focus::Selected),
+            (Action::Filter, Focus::Table) => self.filtering = true,
+            (Action::ClearFilter, Focus::Table) => self.filter_input(KeyCode::Esc),
+            (Action::JumpToNow, Focus::Table) => self.jump_to_now(),
+            (Action::CycleCalendar, Focus::Table) => self.cycle_calendar(),
+            (Action::Export, Focus::Table) => self.export(),
+            (Action::Refresh, Focus::Table) => self.refresh(),
+            (Action::CycleAccount, Focus::Table) => self.cycle_account(),
+            (Action::WorkingHours, Focus::Table) => self.toggle_working_hours(),
+            (Action::CycleTheme, Focus::Table | Focus::Week | Focus::FreeBusy) => {
+                self.cycle_theme()
+            }
+            (Action::Next, Focus::Table) => self.next(),
+            (Action::Prev, Focus::Table) => self.previous(),
+            (Action::Next, Focus::Selected) => {
+                self.detail_scroll = self.detail_scroll.saturating_add(1)
+            }
+            (Action::Prev, Focus::Selected) => {
+                self.detail_scroll = self.detail_scroll.saturating_sub(1)
+            }
+            (Action::Accept, Focus::Selected) => self.respond(EventResponse::Accepted),
+            (Action::Decline, Focus::Selected) => self.respond(EventResponse::Declined),
+            (Action::OpenLink, Focus::Selected) => self.open_link(),
+            (Action::RawBody, Focus::Selected) => self.show_raw_body = !self.show_raw_body,
+            (Action::CopyLink, Focus::Selected) => self.copy_link(),
+            (Action::Dismiss, Focus::Popup) => self.dismiss(),
+            (Action::Snooze, Focus::Popup) => self.snooze(),
+            (Action::OpenLink, Focus::Popup) => self.join_alert(),
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn filter_input(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Char(c) => self.filter.push(c),
+            KeyCode::Backspace => {
+                self.filter.pop();
+            }
+            // Keep the filter applied.
+            KeyCode::Enter => self.filtering = false,
+            KeyCode::Esc => {
+                self.filter.clear();
+                self.filtering = false;
+            }
+            _ => return,
+        }
+        self.relist();
+        let first = (self.visible_count() > 0).then_some(0);
+        self.table_state.select(first);
+    }
+
+    fn listing(&self) -> &Listing {
+        self.listing.get_or_init(|| {
+            let mut events = self
+                .filtered_events()
+                .filter(|event| !self.hidden_by_working_hours(event))
+                .collect::<Vec<_>>();
+            CONFIG.get().unwrap().sort.apply(&mut events);
+            let outside_hours = self
+                .filtered_events()
+                .filter(|event| self.hidden_by_working_hours(event))
+                .count();
+            Listing {
+                ids: events.iter().map(|event| event.id.clone()).collect(),
+                outside_hours,
+                layout: TableLayout::new(&events, outside_hours),
+            }
+        })
+    }
+
+    // Drops the listing, so it's worked out again from the current events and filters.
+    fn relist(&mut self) {
+        self.listing.take();
+    }
+
+    // Events matching the filter, in display order. Selection indexes into this.
+    // In the configured [sort] order, which the table and selection both follow.
+    pub fn visible_events(&self) -> impl Iterator<Item = &CalendarEvent> {
+        self.listing()
+            .ids
+            .iter()
+            .filter_map(|id| self.events.get(id))
+    }
+
+    pub fn visible_event(&self, index: usize) -> Option<&CalendarEvent> {
+        self.listing()
+            .ids
+            .get(index)
+            .and_then(|id| self.events.get(id))
+    }
+
+    pub fn visible_count(&self) -> usize {
+        self.listing().ids.len()
+    }
+
+    pub fn table_layout(&self) -> &TableLayout {
+        &self.listing().layout
+    }
+
+    // Events the filters would show but working hours currently hide.
+    pub fn outside_hours_count(&self) -> usize {
+        self.listing().outside_hours
+    }
+
+    fn hidden_by_working_hours(&self, event: &CalendarEvent) -> bool {
+        self.hide_outside_hours
+            && CONFIG
+                .get()
+                .unwrap()
+                .working_hours
+                .is_some_and(|hours| hours.excludes(event))
+    }
+
+    fn filtered_events(&self) -> impl Iterator<Item = &CalendarEvent> {
+        let filter = self.filter.to_lowercase();
+        let hide_free = CONFIG.get().unwrap().hide_free_events;
+        self.events_by_start().into_iter().filter(move |event| {
+            !(hide_free && event.show_as == ShowAs::Free)
+                && (self.account_filter.is_none() || event.account == self.account_filter)
+                && (filter.is_empty()
+                    || event.subject.to_lowercase().contains(&filter)
+                    || event.organizer.to_lowercase().contains(&filter))
+        })
+    }
+
+    // Every listed event, earliest first.
+    pub fn events_by_start(&self) -> Vec<&CalendarEvent> {
+        let mut events = self.events.values().collect::<Vec<_>>();
+        events.sort_by(|a, b| (a.start_time, &a.id).cmp(&(b.start_time, &b.id)));
+        events
+    }
+
+    // Polls another source alongside the configured provider, e.g. when embedding the app.
+    pub fn with_provider<P: CalendarProvider + Send + Sync + 'static>(self, provider: P) -> Self {
+        self.backend.spawn_provider(provider);
+        self
+    }
+
+    // Adds an event from outside any provider, alerting for it like a fetched one. It stays until
+    // it ends, since no poll will report it removed.
+    pub fn inject_event(&mut self, event: CalendarEvent) {
+        if let Some(id) = self.add_event(event) {
+            self.spawn_timer(&id);
+        }
+    }
+
+    // Returns the ID when a timer is needed, i.e. the event is new or has moved.
+    pub fn add_event(&mut self, event: CalendarEvent) -> Option<String> {
+        // Beyond the window, e.g. from a feed that ignores the requested range. One rescheduled
+        // out of it is dropped rather than left at its old time.
+        let horizon = Utc::now() + chrono::Duration::days(CONFIG.get().unwrap().limit_days as i64);
+        if event.start_time > horizon {
+            self.remove_event(&event.id);
+            return None;
+        }
+        self.relist();
+
+        let previous = self.find_event(&event.id);
+        let timer = previous.is_none_or(|existing| existing.start_time != event.start_time);
+        let changes = previous.map_or(Vec::new(), |existing| describe_changes(existing, &event));
+        if !changes.is_empty() {
+            self.announce_change(&event, &changes);
+        }
+        let id = event.id.clone();
+
+        // Updated in place while it would merge the same way, so equally complete copies don't
+        // take turns being listed.
+        match self.events.get_mut(&id) {
+            Some(existing)
+                if existing.start_time == event.start_time
+                    && existing.i_cal_uid == event.i_cal_uid =>
+            {
+                let mut event = event;
+                event.also_in = std::mem::take(&mut existing.also_in);
+                *existing = event;
+            }
+            _ => {
+                self.detach(&id);
+                self.place(event);
+            }
+        }
+        timer.then_some(id)
+    }
+
+    // A listed event or a copy of one, by ID.
+    fn find_event(&self, id: &str) -> Option<&CalendarEvent> {
+        self.events
+            .get(id)
+            .or_else(|| self.copies.values().flatten().find(|copy| copy.id == id))
+    }
+
+    // The same meeting read through another calendar, e.g. an invite that is also on a team
+    // calendar. Only the fuller copy is listed, noting where else it appears, and the rest are
+    // kept so the meeting stays listed until every copy is gone.
+    fn place(&mut self, event: CalendarEvent) {
+        let listed = self
+            .events
+            .values()
+            .find(|existing| {
+                existing.i_cal_uid.is_some()
+                    && existing.i_cal_uid == event.i_cal_uid
+                    && existing.start_time == event.start_time
+            })
+            .map(|existing| existing.id.clone());
+        let Some(listed) = listed else {
+            self.events.insert(event.id.clone(), event);
+            return;
+        };
+
+        let mut copies = self.copies.remove(&listed).unwrap_or_default();
+        let id = match event.completeness() <= self.events[&listed].completeness() {
+            true => {
+                copies.push(event);
+                listed
+            }
+            false => {
+                copies.extend(self.events.remove(&listed));
+                let id = event.id.clone();
+                self.events.insert(id.clone(), event);
+                id
+            }
+        };
+        self.copies.insert(id.clone(), copies);
+        self.note_copies(&id);
+    }
+
+    // Takes an event or copy out, listing the fullest remaining copy in place of a listed one.
+    fn detach(&mut self, id: &str) {
+        if self.events.remove(id).is_some() {
+            let Some(mut copies) = self.copies.remove(id) else {
+                return;
+            };
+            // The earliest seen of the fullest copies.
+            let Some(fullest) = (0..copies.len())
+                .rev()
+                .max_by_key(|i| copies[*i].completeness())
+            else {
+                return;
+            };
+            let event = copies.remove(fullest);
+            let id = event.id.clone();
+            self.events.insert(id.clone(), event);
+            if !copies.is_empty() {
+                self.copies.insert(id.clone(), copies);
+            }
+            self.note_copies(&id);
+            return;
+        }
+
+        let Some(listed) = self
+            .copies
+            .iter()
+            .find(|(_, copies)| copies.iter().any(|copy| copy.id == id))
+            .map(|(listed, _)| listed.clone())
+        else {
+            return;
+        };
+        if let Some(copies) = self.copies.get_mut(&listed) {
+            copies.retain(|copy| copy.id != id);
+            if copies.is_empty() {
+                self.copies.remove(&listed);
+            }
+        }
+        self.note_copies(&listed);
+    }
+
+    // Where else a listed event appears, from the copies merged into it.
+    fn note_copies(&mut self, id: &str) {
+        let Some(event) = self.events.get_mut(id) else {
+            return;
+        };
+        let source = event.source().map(str::to_string);
+        event.also_in.clear();
+        for copy in self.copies.get(id).into_iter().flatten() {
+            let name = copy.source().unwrap_or("another calendar").to_string();
+            if Some(&name) != source.as_ref() && !event.also_in.contains(&name) {
+                event.also_in.push(name);
+            }
+        }
+    }
+
+    pub fn respond(&mut self, response: EventResponse) {
+        if !matches!(CONFIG.get().unwrap().provider, Provider::Outlook) {
+            self.status = Some("Responding is only supported for Outlook".to_string());
+            return;
+        }
+        let Some(event) = self.selected_event() else {
+            return;
+        };
+        let id = event.id.clone();
+        let Some((account, token)) = CONFIG
+            .get()
+            .unwrap()
+            .outlook_account(event.account.as_deref())
+            .zip(self.backend.token(event.account.as_deref()))
+        else {
+            return;
+        };
+        if !account.outlook.can_respond() {
+            self.status = Some(
+                "Responding needs the Calendars.ReadWrite scope in [outlook] scopes".to_string(),
+            );
+            return;
+        }
+        let Some(event) = self.events.get_mut(&id) else {
+            return;
+        };
+
+        // Optimistic; the next refresh reconciles it with Graph.
+        event.response = Some(response.clone());
+        let message = format!("{response}: {}", event.subject);
+        self.status = Some(format!("Sending {response}..."));
+
+        let action_tx = self.backend.action_tx.clone();
+        let events_url = account.outlook.events_url();
+        self.backend.data.spawn(async move {
+            let token = token.read().unwrap().clone();
+            let result = respond(&http_client(), &events_url, &token, &id, &response)
+                .await
+                .map(|_| message);
+            _ = action_tx.send(result);
+        });
+    }
+
+    // Selects the given event again, or the next one by time if it is gone.
+    pub fn reselect(&mut self, selected: Option<(String, DateTime<Utc>)>) {
+        // Nothing to keep: select the first row, or none while the table is empty.
+        let Some((id, start_time)) = selected else {
+            let first = (self.visible_count() > 0).then_some(0);
+            self.table_state.select(first);
+            return;
+        };
+        // Still on its row, as on most passes of the event loop.
+        if self.selected_event().is_some_and(|event| event.id == id) {
+            return;
+        }
+        let index = self
+            .visible_events()
+            .position(|event| event.id == id)
+            .or_else(|| {
+                self.visible_events()
+                    .enumerate()
+                    .filter(|(_, event)| event.start_time >= start_time)
+                    .min_by_key(|(_, event)| event.start_time)
+                    .map(|(i, _)| i)
+            })
+            .or_else(|| self.visible_count().checked_sub(1));
+        self.table_state.select(index);
+    }
+
+    // The full event once its details have been loaded.
+    pub fn selected_event(&self) -> Option<&CalendarEvent> {
+        self.table_state
+            .selected()
+            .and_then(|i| self.visible_event(i))
+            .map(|event| self.details.get(&event.id).unwrap_or(event))
+    }
+
+    // Loads every field of the selected event, when polls are limited by [outlook] select. Done
+    // on each opening, so the details don't go stale.
+    fn fetch_details(&mut self) {
+        if !matches!(CONFIG.get().unwrap().provider, Provider::Outlook) {
+            return;
+        }
+        let Some(event) = self.selected_event() else {
+            return;
+        };
+        let Some((account, token)) = CONFIG
+            .get()
+            .unwrap()
+            .outlook_account(event.account.as_deref())
+            .zip(self.backend.token(event.account.as_deref()))
+        else {
+            return;
+        };
+        if account.outlook.select.is_empty() {
+            return;
+        }
+
+        let url = format!("{}/{}", account.outlook.events_url(), event.id);
+        let calendar_name = event.calendar_name.clone();
+        let account = event.account.clone();
+        let detail_tx = self.backend.detail_tx.clone();
+        let action_tx = self.backend.action_tx.clone();
+        self.backend.data.spawn(async move {
+            let token = token.read().unwrap().clone();
+            match outlook::fetch_event(&http_client(), &url, &token).await {
+                Ok(mut event) => {
+                    event.calendar_name = calendar_name;
+                    event.account = account;
+                    _ = detail_tx.send(event);
+                }
+                Err(error) => {
+                    _ = action_tx.send(Err(format!("Could not load the event: {error}")));
+                }
+            }
+        });
+    }
+
+    pub fn open_link(&mut self) {
+        let Some(url) = self
+            .selected_event()
+            .and_then(|event| event.meeting_url())
+            .map(str::to_string)
+        else {
+            self.status = Some("No meeting link".to_string());
+            return;
+        };
+        self.open_url(&url);
+    }
+
+    // Joins the meeting being alerted for, closing the alert on the way.
+    pub fn join_alert(&mut self) {
+        let Some(url) = self
+            .alert_event()
+            .and_then(|event| event.meeting_url())
+            .map(str::to_string)
+        else {
+            self.status = Some("No meeting link".to_string());
+            return;
+        };
+        self.open_url(&url);
+        self.dismiss();
+    }
+
+    fn open_url(&mut self, url: &str) {
+        if let Err(error) = webbrowser::open(url) {
+            self.status = Some(format!("Could not open meeting link: {error}"));
+        }
+    }
+
+    // Starts the countdown to opening an accepted meeting's link.
+    pub fn queue_join(&mut self, id: String, start_time: DateTime<Utc>) {
+        if !self.is_scheduled(&id, start_time) {
+            return;
+        }
+        let Some(event) = self.events.get(&id) else {
+            return;
+        };
+        if !matches!(
+            event.response,
+            Some(EventResponse::Accepted | EventResponse::Organizer)
+        ) || event.meeting_url().is_none()
+            || !self.joined.insert(id.clone())
+        {
+            return;
+        }
+        let delay = CONFIG.get().unwrap().auto_join_delay_seconds;
+        self.pending_join = Some((id, Utc::now() + chrono::Duration::seconds(delay as i64)));
+    }
+
+    // Counts down in the status line, then opens the link.
+    pub fn tick_join(&mut self) {
+        let Some((id, join_at)) = self.pending_join.clone() else {
+            return;
+        };
+        let Some(event) = self.events.get(&id) else {
+            self.pending_join = None;
+            return;
+        };
+        let remaining = (join_at - Utc::now()).num_seconds();
+        if remaining > 0 {
+            self.status = Some(format!(
+                "Joining {} in {remaining}s ({} to cancel)",
+                event.subject,
+                CONFIG.get().unwrap().keybindings.keys(Action::Dismiss)
+            ));
+            return;
+        }
+
+        let url = event.meeting_url().map(str::to_string);
+        self.status = Some(format!("Joined {}", event.subject));
+        self.pending_join = None;
+        if let Some(url) = url {
+            self.open_url(&url);
+        }
+    }
+
+    pub fn copy_link(&mut self) {
+        let Some(url) = self
+            .selected_event()
+            .and_then(|event| event.meeting_url())
+            .map(str::to_string)
+        else {
+            self.status = Some("No meeting link".to_string());
+            return;
+        };
+
+        // Fails on headless sessions with no clipboard provider.
+        let result = Clipboard::new().and_then(|mut clipboard| clipboard.set_text(url));
+        self.status = Some(match result {
+            Ok(_) => "Copied meeting link".to_string(),
+            Err(error) => format!("Could not copy meeting link: {error}"),
+        });
+    }
+
+    pub fn remove_event(&mut self, id: &str) {
+        self.relist();
+        self.detach(id);
+        self.details.remove(id);
+    }
+
+    pub fn retry_auth(&mut self) {
+        self.auth_error = self.backend.start().err();
+        if self.auth_error.is_none() {
+            self.focus = Focus::Table;
         }
-        None
     }
 
     pub fn set_focus(&mut self, focus: Focus) {
+        if let Focus::Selected = focus {
+            self.detail_scroll = 0;
+            self.show_raw_body = false;
+        }
         self.focus = focus;
     }
 
-    pub fn poll_calendar_events(&self) -> Option<CalendarEvent> {
+    pub fn poll_calendar_events(&self) -> Option<EventCommand> {
         self.backend.event_rx.try_iter().next()
     }
 
-    pub fn spawn_timer(&self, end: DateTime<Utc>) {
-        let eta = end
-            .checked_sub_signed(chrono::Duration::minutes(
-                CONFIG.get().unwrap().notification_period_minutes,
-            )) // TODO: Make reminder offset configurable
-            .map(|x| x.signed_duration_since(Utc::now()).num_milliseconds())
-            .unwrap();
+    // Copies get timers too, in case they come to be listed; only listed events alert.
+    pub fn spawn_timer(&self, id: &str) {
+        let Some(event) = self.find_event(id) else {
+            return;
+        };
+        let end = event.start_time;
+        // All-day events start at midnight, so a "starting soon" alert is just noise.
+        if event.is_all_day {
+            return;
+        }
+
+        // One timer per reminder offset, each reporting which offset fired.
+        for offset in CONFIG.get().unwrap().notification_offsets() {
+            let eta = end
+                .checked_sub_signed(chrono::Duration::minutes(offset))
+                .map(|x| x.signed_duration_since(Utc::now()).num_milliseconds());
+
+            // Skip offsets already in the past for events starting soon.
+            if let Some(eta) = eta.filter(|eta| *eta >= 0) {
+                self.schedule_alert(eta as u64, event.id.clone(), end, offset);
+            }
+        }
+
+        if CONFIG.get().unwrap().auto_join {
+            let eta = end.signed_duration_since(Utc::now()).num_milliseconds();
+            if eta >= 0 {
+                let join_tx = self.backend.join_tx.clone();
+                let id = event.id.clone();
+                self.backend.timer.spawn(async move {
+                    sleep(Duration::from_millis(eta as u64)).await;
+                    _ = join_tx.send((id, end));
+                });
+            }
+        }
+    }
 
+    // Timers aren't cancelled when an event moves, so each carries the start it was set for.
+    pub fn schedule_alert(
+        &self,
+        eta_millis: u64,
+        id: String,
+        start_time: DateTime<Utc>,
+        offset: i64,
+    ) {
         let timer_tx = self.backend.timer_tx.clone();
         self.backend.timer.spawn(async move {
-            sleep(Duration::from_millis(eta as u64)).await;
+            sleep(Duration::from_millis(eta_millis)).await;
             timer_tx
-                .send(())
+                .send((id, start_time, offset))
                 .expect("ERROR: Could not send timer notification");
         });
     }
 
-    pub fn poll_timers(&self) -> bool {
-        self.backend.timer_rx.try_recv().is_ok()
+    // Whether a timer set for this start still applies: the event is listed and hasn't moved.
+    fn is_scheduled(&self, id: &str, start_time: DateTime<Utc>) -> bool {
+        self.events
+            .get(id)
+            .is_some_and(|event| event.start_time == start_time)
+    }
+
+    pub fn dismiss(&mut self) {
+        self.focus = Focus::Table;
+        self.alert_id = None;
+        self.alert_offset = None;
+        self.popup_until = None;
+        self.change_notice = None;
+        // Toggle the external notification (e.g. zellij floating panes) back.
+        self.run_notification_command();
+    }
+
+    pub fn snooze(&mut self) {
+        let snooze_minutes = CONFIG.get().unwrap().snooze_minutes;
+        if let Some(event) = self.alert_event() {
+            let snoozed_until = Utc::now() + chrono::Duration::minutes(snooze_minutes);
+            let offset = event
+                .start_time
+                .signed_duration_since(snoozed_until)
+                .num_minutes();
+            let id = event.id.clone();
+            let start_time = event.start_time;
+            self.schedule_alert(
+                (snooze_minutes * 60 * 1000).max(0) as u64,
+                id,
+                start_time,
+                offset,
+            );
+        }
+        self.dismiss();
+    }
+
+    // The event whose timer raised the current alert.
+    pub fn alert_event(&self) -> Option<&CalendarEvent> {
+        let id = self.alert_id.as_ref()?;
+        self.events.get(id)
+    }
+
+    pub fn poll_timers(&self) -> Option<(String, DateTime<Utc>, i64)> {
+        self.backend.timer_rx.try_recv().ok()
     }
 
     pub fn popup(&mut self) {
         self.focus = Focus::Popup;
-        _ = Command::new("zellij")
-            .args(["action", "toggle-floating-panes"])
-            .status()
-            .expect("ERROR: Could not send command to Zellij");
+        self.popup_until = CONFIG
+            .get()
+            .unwrap()
+            .popup_auto_dismiss_seconds
+            .map(|seconds| Utc::now() + chrono::Duration::seconds(seconds as i64));
+        self.run_notification_command();
+    }
+
+    pub fn run_notification_command(&mut self) {
+        // e.g. ["zellij", "action", "toggle-floating-panes"]
+        let Some((program, args)) = CONFIG
+            .get()
+            .unwrap()
+            .notification_command
+            .as_ref()
+            .and_then(|command| command.split_first())
+        else {
+            return;
+        };
+
+        match Command::new(program).args(args).status() {
+            Ok(status) if status.success() => (),
+            Ok(status) => self.status = Some(format!("Notification command {status}")),
+            Err(error) => self.status = Some(format!("Notification command failed: {error}")),
+        }
+    }
+
+    pub fn send_desktop_notification(&mut self) {
+        if !CONFIG.get().unwrap().desktop_notifications {
+            return;
+        }
+
+        let Some(event) = self.alert_event() else {
+            return;
+        };
+        let minutes = self.alert_offset.unwrap_or_else(|| {
+            event
+                .start_time
+                .signed_duration_since(Utc::now())
+                .num_minutes()
+        });
+        // The template's first line is the title, the rest goes in the body.
+        let text = CONFIG.get().unwrap().alert_template.render(event);
+        let (summary, details) = text.split_once('\n').unwrap_or((&text, ""));
+        let body = match details {
+            "" => format!("Starts in {minutes} minutes"),
+            details => format!("{details}\nStarts in {minutes} minutes"),
+        };
+        self.show_notification(summary, &body);
+    }
+
+    fn show_notification(&mut self, summary: &str, body: &str) {
+        let result = Notification::new().summary(summary).body(body).show();
+
+        // No notification daemon running, etc.
+        if let Err(error) = result {
+            self.status = Some(format!("Desktop notification failed: {error}"));
+        }
+    }
+
+    // Raised when an event already shown comes back with different times or location.
+    fn announce_change(&mut self, event: &CalendarEvent, changes: &[String]) {
+        let heading = if changes.iter().all(|change| change.starts_with("Location")) {
+            "Meeting location changed"
+        } else {
+            "Meeting rescheduled"
+        };
+        let notice = format!("{heading}: {}\n{}", event.subject, changes.join("\n"));
+
+        // Never cover an alert that hasn't been answered yet.
+        if matches!(self.focus, Focus::Popup) {
+            self.status = Some(notice.replace('\n', "; "));
+        } else {
+            self.change_notice = Some(notice);
+            self.popup();
+        }
+
+        if CONFIG.get().unwrap().desktop_notifications {
+            let summary = format!("{heading}: {}", event.subject);
+            self.show_notification(&summary, &changes.join("\n"));
+        }
+    }
+
+    // Polls every provider now; the regular schedule carries on from there.
+    pub fn refresh(&mut self) {
+        if self.backend.refresh_tx.send(()).is_ok() {
+            self.app_status = AppStatus::Fetching;
+        }
+    }
+
+    // Everything currently loaded, filter or not.
+    pub fn export(&mut self) {
+        let path = config_dir().join("export.ics");
+        self.status = Some(
+            match fs::write(&path, ics::write_calendar(self.events_by_start())) {
+                Ok(()) => format!(
+                    "Exported {} events to {}",
+                    self.events.len(),
+                    path.display()
+                ),
+                Err(error) => format!("Export failed: {error}"),
+            },
+        );
+    }
+
+    // Played on its own thread, so a long sound doesn't hold up the UI.
+    pub fn play_alert_sound(&self) {
+        let Some(sound) = CONFIG.get().unwrap().alert_sound.clone() else {
+            return;
+        };
+        thread::spawn(move || {
+            // No audio device, a missing file or an unsupported format still gets a beep.
+            if sound.as_os_str() == "bell" || play_sound(&sound).is_err() {
+                let mut stdout = std::io::stdout();
+                _ = stdout.write_all(b"\x07");
+                _ = stdout.flush();
+            }
+        });
     }
 
     pub fn next(&mut self) {
+        let len = self.visible_count();
+        if len == 0 {
+            return;
+        }
         let i = match self.table_state.selected() {
             Some(i) => {
-                if i >= self.events.len() - 1 {
+                if i >= len - 1 {
                     0
                 } else {
                     i + 1
@@ -160,12 +1209,133 @@ impl App {
         self.table_state.select(Some(i));
     }
 
-    pub fn previous(&mut self) {
-        let i = match self.table_state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.events.len() - 1
-                } else {
+    // Selects the next event whose subject starts with what has been typed. A new prefix moves on
+    // past the selection so repeating a letter cycles through its matches; a longer one may stay.
+    pub fn type_ahead(&mut self, c: char) {
+        if !self.typing_ahead() {
+            self.type_ahead.clear();
+        }
+        self.type_ahead_at = Instant::now();
+        self.type_ahead.extend(c.to_lowercase());
+
+        let subjects = self
+            .visible_events()
+            .map(|event| event.subject.to_lowercase())
+            .collect::<Vec<_>>();
+        if subjects.is_empty() {
+            return;
+        }
+        let selected = self.table_state.selected().unwrap_or(0);
+        let skip = usize::from(self.type_ahead.chars().count() == 1);
+        let found = (0..subjects.len())
+            .map(|i| (selected + skip + i) % subjects.len())
+            .find(|&i| subjects[i].starts_with(&self.type_ahead));
+        if found.is_some() {
+            self.table_state.select(found);
+        }
+    }
+
+    fn typing_ahead(&self) -> bool {
+        !self.type_ahead.is_empty() && self.type_ahead_at.elapsed() <= TYPE_AHEAD_TIMEOUT
+    }
+
+    pub fn jump_to_now(&mut self) {
+        let now = Utc::now();
+        // The meeting in progress, or else the next one.
+        let upcoming = self
+            .visible_events()
+            .enumerate()
+            .filter(|(_, event)| event.end_time > now)
+            .min_by_key(|(_, event)| event.start_time)
+            .map(|(i, _)| i);
+        let last = self.visible_count().checked_sub(1);
+        self.table_state.select(upcoming.or(last));
+    }
+
+    pub fn toggle_working_hours(&mut self) {
+        if CONFIG.get().unwrap().working_hours.is_none() {
+            self.status = Some("Set [working_hours] to hide events outside them".to_string());
+            return;
+        }
+        let selected = self
+            .selected_event()
+            .map(|event| (event.id.clone(), event.start_time));
+        self.hide_outside_hours = !self.hide_outside_hours;
+        self.relist();
+        self.reselect(selected);
+    }
+
+    // Steps through showing each of the [[accounts]] on its own, then all of them again.
+    pub fn cycle_account(&mut self) {
+        let accounts = &CONFIG.get().unwrap().accounts;
+        if accounts.is_empty() {
+            self.status = Some("No [[accounts]] to switch between".to_string());
+            return;
+        }
+        let next = match &self.account_filter {
+            None => 0,
+            Some(name) => accounts
+                .iter()
+                .position(|account| account.name == *name)
+                .map_or(0, |i| i + 1),
+        };
+        self.account_filter = accounts.get(next).map(|account| account.name.clone());
+        self.status = Some(match &self.account_filter {
+            Some(name) => format!("Showing {name}"),
+            None => "Showing every account".to_string(),
+        });
+        self.relist();
+        let first = (self.visible_count() > 0).then_some(0);
+        self.table_state.select(first);
+    }
+
+    // Previews the built-in palettes, starting from the first when a custom [theme] is in use.
+    pub fn cycle_theme(&mut self) {
+        if CONFIG.get().unwrap().no_color() {
+            self.status = Some("Themes are off while no_color is set".to_string());
+            return;
+        }
+        let index = self.palette.map_or(0, |index| (index + 1) % PALETTES.len());
+        self.palette = Some(index);
+        self.colors = TableColors::new(&PALETTES[index]);
+        self.status = Some(format!("Theme {index}"));
+    }
+
+    // Moves on to the next Outlook calendar. The events shown switch over on the next refresh.
+    pub fn cycle_calendar(&mut self) {
+        if !CONFIG.get().unwrap().accounts.is_empty() {
+            self.status = Some("Calendars are set per account in [[accounts]]".to_string());
+            return;
+        }
+        if !CONFIG.get().unwrap().outlook.calendar_ids.is_empty() {
+            self.status = Some("Showing every calendar in [outlook] calendar_ids".to_string());
+            return;
+        }
+        let calendars = self.backend.calendars.read().unwrap();
+        if calendars.is_empty() {
+            self.status = Some("No other calendars to switch to".to_string());
+            return;
+        }
+        let mut calendar_id = self.backend.calendar_id.write().unwrap();
+        let current = calendars.iter().position(|calendar| match &*calendar_id {
+            Some(id) => calendar.id == *id,
+            None => calendar.is_default,
+        });
+        let next = &calendars[current.map_or(0, |i| (i + 1) % calendars.len())];
+        *calendar_id = Some(next.id.clone());
+        self.status = Some(format!("Switching to {}...", next.name));
+    }
+
+    pub fn previous(&mut self) {
+        let len = self.visible_count();
+        if len == 0 {
+            return;
+        }
+        let i = match self.table_state.selected() {
+            Some(i) => {
+                if i == 0 {
+                    len - 1
+                } else {
                     i - 1
                 }
             }
@@ -175,31 +1345,1123 @@ impl App {
     }
 }
 
+fn play_sound(path: &Path) -> Result<(), Box<dyn Error>> {
+    let (_stream, handle) = OutputStream::try_default()?;
+    let sink = Sink::try_new(&handle)?;
+    sink.append(Decoder::new(BufReader::new(File::open(path)?))?);
+    sink.sleep_until_end();
+    Ok(())
+}
+
+// One line per watched field that differs, e.g. "Start: Tue 4 Mar 10:00 → Wed 5 Mar 14:00".
+fn describe_changes(previous: &CalendarEvent, event: &CalendarEvent) -> Vec<String> {
+    let when = |time: DateTime<Utc>| {
+        let time = to_display(time);
+        format!("{} {}", time.format("%a %-d %b"), format_time(&time))
+    };
+    let place = |location: &str| match location {
+        "" => "none".to_string(),
+        location => location.to_string(),
+    };
+
+    CONFIG
+        .get()
+        .unwrap()
+        .notify_changes
+        .iter()
+        .filter_map(|field| match field {
+            ChangeField::Start if previous.start_time != event.start_time => Some(format!(
+                "Start: {} → {}",
+                when(previous.start_time),
+                when(event.start_time)
+            )),
+            ChangeField::End if previous.end_time != event.end_time => Some(format!(
+                "End: {} → {}",
+                when(previous.end_time),
+                when(event.end_time)
+            )),
+            ChangeField::Location if previous.location != event.location => Some(format!(
+                "Location: {} → {}",
+                place(&previous.location),
+                place(&event.location)
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
-    pub theme: usize,
-    pub notification_period_minutes: i64,
+    pub theme: Theme,
+    // Stripe the table's rows in normal_row_color and alt_row_color.
+    #[serde(default = "default_alternating_rows")]
+    pub alternating_rows: bool,
+    #[serde(default)]
+    pub selection_style: SelectionStyle,
+    // Smallest terminal the UI is drawn in; anything smaller shows a resize hint.
+    #[serde(default = "default_min_width")]
+    pub min_width: u16,
+    #[serde(default = "default_min_height")]
+    pub min_height: u16,
+    // Superseded by notification_offsets_minutes, still accepted for older configs.
+    pub notification_period_minutes: Option<i64>,
+    #[serde(default)]
+    pub notification_offsets_minutes: Vec<i64>,
+    #[serde(default = "default_snooze_minutes")]
+    pub snooze_minutes: i64,
+    // Close unanswered alerts after this long, rather than leaving them up until a key is pressed.
+    pub popup_auto_dismiss_seconds: Option<u64>,
+    #[serde(default)]
+    pub alert_template: AlertTemplate,
     pub refresh_period_seconds: u32,
     pub limit_days: u64,
     pub auth_timeout_millis: u64,
+    pub notification_command: Option<Vec<String>>,
+    #[serde(default)]
+    pub desktop_notifications: bool,
+    // Fields that raise a notice when they change on an event already listed.
+    #[serde(default = "default_notify_changes")]
+    pub notify_changes: Vec<ChangeField>,
+    // Ask before quitting, so a stray q during an alert doesn't close the app.
+    #[serde(default)]
+    pub confirm_quit: bool,
+    // Open the meeting link of accepted meetings when they start.
+    #[serde(default)]
+    pub auto_join: bool,
+    // Grace period to cancel an auto-join in.
+    #[serde(default = "default_auto_join_delay_seconds")]
+    pub auto_join_delay_seconds: u64,
+    // Sound file to play on alerts, or "bell" for the terminal bell.
+    pub alert_sound: Option<PathBuf>,
+    // Rotated daily; defaults to cal-tui.log next to the config.
+    pub log_file: Option<PathBuf>,
+    // off, error, warn, info, debug or trace.
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    #[serde(default)]
+    pub provider: Provider,
+    #[serde(default)]
+    pub ics_urls: Vec<String>,
+    #[serde(default)]
     pub outlook: OutlookConfig,
+    // Replaces [outlook] when given, merging the events of every account.
+    #[serde(default)]
+    pub accounts: Vec<AccountConfig>,
+    #[serde(default)]
+    pub google: GoogleConfig,
+    #[serde(default)]
+    pub caldav: CalDavConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub keybindings: Keybindings,
+    #[serde(default)]
+    pub time_format: TimeFormat,
+    // IANA name such as "Europe/London", to show times in instead of the system's zone.
+    pub display_timezone: Option<String>,
+    #[serde(default = "default_columns")]
+    pub columns: Vec<Column>,
+    #[serde(default)]
+    pub category_colors: CategoryColors,
+    pub working_hours: Option<WorkingHours>,
+    #[serde(default)]
+    pub sort: Sort,
+    // Open time at least this long is highlighted in the free/busy view.
+    #[serde(default = "default_free_slot_minutes")]
+    pub free_slot_minutes: u64,
+    // Leave out events marked free, such as reminders and FYI invites.
+    #[serde(default)]
+    pub hide_free_events: bool,
+    #[serde(default)]
+    pub auth_flow: AuthFlow,
+    // Attributes such as bold and reverse only, for screen readers and terminals without color.
+    #[serde(default)]
+    no_color: bool,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeField {
+    Start,
+    End,
+    Location,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthFlow {
+    // Local browser redirecting to the bundled server.
+    #[default]
+    Browser,
+    // Code entered on any device, for SSH and headless sessions.
+    DeviceCode,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+pub enum TimeFormat {
+    #[serde(rename = "12h")]
+    TwelveHour,
+    #[default]
+    #[serde(rename = "24h")]
+    TwentyFourHour,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Subject,
+    Start,
+    End,
+    Duration,
+    Location,
+    Organizer,
+    Response,
+    Account,
+}
+
+impl Column {
+    pub fn title(&self) -> &'static str {
+        match self {
+            Column::Subject => "Event",
+            Column::Start => "Start Time",
+            Column::End => "End Time",
+            Column::Duration => "Duration",
+            Column::Location => "Location",
+            Column::Organizer => "Organizer",
+            Column::Response => "Response",
+            Column::Account => "Account",
+        }
+    }
+
+    // Share of the table's width, relative to the other columns shown.
+    pub fn weight(&self) -> u32 {
+        match self {
+            Column::Subject | Column::Start => 4,
+            Column::Location | Column::Organizer => 3,
+            Column::End | Column::Response | Column::Account => 2,
+            Column::Duration => 1,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct OutlookConfig {
     pub client_id: String,
+    #[serde(default = "default_outlook_base_url")]
     pub base_url: String,
+    #[serde(default = "default_redirect_port")]
+    pub redirect_port: u16,
+    // login, consent, select_account or none; empty leaves it to Microsoft.
+    #[serde(default = "default_outlook_prompt")]
+    pub prompt: String,
+    #[serde(default = "default_outlook_scopes")]
+    pub scopes: Vec<String>,
+    // Unset reads the account's default calendar.
+    #[serde(default)]
+    pub calendar_id: Option<String>,
+    // Several calendars shown together; takes precedence over calendar_id.
+    #[serde(default)]
+    pub calendar_ids: Vec<String>,
+    // Graph fields polls ask for, to keep responses small. Empty asks for everything.
+    #[serde(default = "default_outlook_select")]
+    pub select: Vec<String>,
+}
+
+// [sort], applied when listing events; they're stored by start time regardless.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+pub struct Sort {
+    #[serde(default)]
+    pub by: SortBy,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Start,
+    End,
+    Subject,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl Sort {
+    // Stable, so ties stay in start order.
+    pub fn apply(&self, events: &mut [&CalendarEvent]) {
+        events.sort_by(|a, b| {
+            let ordering = match self.by {
+                SortBy::Start => a.start_time.cmp(&b.start_time),
+                SortBy::End => a.end_time.cmp(&b.end_time),
+                SortBy::Subject => a.subject.to_lowercase().cmp(&b.subject.to_lowercase()),
+            };
+            match self.order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            }
+        });
+    }
+}
+
+// [working_hours], as "HH:MM" in the display timezone.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "WorkingHoursConfig")]
+pub struct WorkingHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+#[derive(Deserialize)]
+struct WorkingHoursConfig {
+    start: String,
+    end: String,
+}
+
+impl TryFrom<WorkingHoursConfig> for WorkingHours {
+    type Error = String;
+
+    fn try_from(config: WorkingHoursConfig) -> Result<Self, Self::Error> {
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .map_err(|_| format!("Working hours must be given as HH:MM, not \"{time}\""))
+        };
+        let hours = WorkingHours {
+            start: parse(&config.start)?,
+            end: parse(&config.end)?,
+        };
+        match hours.start < hours.end {
+            true => Ok(hours),
+            false => Err("Working hours must start before they end".to_string()),
+        }
+    }
+}
+
+impl WorkingHours {
+    // Whether none of the event falls within working hours on the day it starts. All-day events
+    // are never outside them.
+    pub fn excludes(&self, event: &CalendarEvent) -> bool {
+        let start = to_display(event.start_time).naive_local();
+        let end = to_display(event.end_time).naive_local();
+        let day = start.date();
+        !event.is_all_day && (end <= day.and_time(self.start) || start >= day.and_time(self.end))
+    }
+}
+
+// Another Microsoft account, e.g. in a client's tenant. Takes the same settings as [outlook].
+#[derive(Debug, Deserialize)]
+pub struct AccountConfig {
+    // Labels the account's events and picks it when filtering.
+    pub name: String,
+    #[serde(flatten)]
+    pub outlook: OutlookConfig,
+}
+
+// An Outlook account to sign in to. Only [[accounts]] entries have a name.
+#[derive(Debug, Clone, Copy)]
+pub struct Account<'a> {
+    pub name: Option<&'a str>,
+    pub outlook: &'a OutlookConfig,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GoogleConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    #[serde(default = "default_google_calendar_id")]
+    pub calendar_id: String,
+    #[serde(default = "default_redirect_port")]
+    pub redirect_port: u16,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CalDavConfig {
+    pub url: String,
+    pub username: String,
+    pub password_command: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NetworkConfig {
+    // Unset falls back to HTTPS_PROXY and friends from the environment.
+    pub proxy_url: Option<String>,
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+    // Only for proxies that intercept TLS with their own certificate.
+    #[serde(default)]
+    pub danger_accept_invalid_certs: bool,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            proxy_url: None,
+            timeout_seconds: default_timeout_seconds(),
+            danger_accept_invalid_certs: false,
+        }
+    }
+}
+
+fn default_alternating_rows() -> bool {
+    true
+}
+
+fn default_min_width() -> u16 {
+    40
+}
+
+fn default_min_height() -> u16 {
+    10
+}
+
+fn default_log_level() -> String {
+    "warn".to_string()
+}
+
+fn default_timeout_seconds() -> u64 {
+    30
+}
+
+fn default_columns() -> Vec<Column> {
+    vec![Column::Subject, Column::Start, Column::Duration]
+}
+
+fn default_free_slot_minutes() -> u64 {
+    30
+}
+
+fn default_auto_join_delay_seconds() -> u64 {
+    10
+}
+
+fn default_notify_changes() -> Vec<ChangeField> {
+    vec![ChangeField::Start, ChangeField::End, ChangeField::Location]
+}
+
+fn default_snooze_minutes() -> i64 {
+    5
+}
+
+fn default_outlook_base_url() -> String {
+    "https://graph.microsoft.com/v1.0/me/calendarView".to_string()
+}
+
+fn default_outlook_prompt() -> String {
+    "select_account".to_string()
+}
+
+// Without offline_access no refresh token is issued, and the session dies with the first
+// bearer. Refreshing never shows UI, so it needs no prompt of its own.
+fn default_outlook_scopes() -> Vec<String> {
+    ["openid", "Calendars.ReadBasic", "offline_access"]
+        .map(String::from)
+        .to_vec()
+}
+
+fn default_google_calendar_id() -> String {
+    "primary".to_string()
+}
+
+// Enough to list events and alert for them; onlineMeeting and responseStatus let alerts offer to
+// join and auto_join tell what was accepted.
+fn default_outlook_select() -> Vec<String> {
+    [
+        "subject",
+        "showAs",
+        "isOnlineMeeting",
+        "onlineMeeting",
+        "responseStatus",
+    ]
+    .into_iter()
+    .map(str::to_string)
+    .collect()
+}
+
+fn default_redirect_port() -> u16 {
+    8000
+}
+
+impl Default for OutlookConfig {
+    fn default() -> Self {
+        Self {
+            client_id: String::new(),
+            base_url: default_outlook_base_url(),
+            redirect_port: default_redirect_port(),
+            prompt: default_outlook_prompt(),
+            scopes: default_outlook_scopes(),
+            calendar_id: None,
+            calendar_ids: Vec::new(),
+            select: default_outlook_select(),
+        }
+    }
+}
+
+impl Default for GoogleConfig {
+    fn default() -> Self {
+        Self {
+            client_id: String::new(),
+            client_secret: String::new(),
+            calendar_id: default_google_calendar_id(),
+            redirect_port: default_redirect_port(),
+        }
+    }
+}
+
+impl OutlookConfig {
+    pub fn redirect_uri(&self) -> String {
+        format!("http://localhost:{}/redirect", self.redirect_port)
+    }
+
+    // Accepting and declining needs write access; ReadBasic and Read only allow reading.
+    pub fn can_respond(&self) -> bool {
+        self.scopes
+            .iter()
+            .any(|scope| scope.eq_ignore_ascii_case("Calendars.ReadWrite"))
+    }
+
+    // e.g. https://graph.microsoft.com/v1.0/me/calendarView -> .../me/events
+    pub fn events_url(&self) -> String {
+        format!("{}/events", self.me_url())
+    }
+
+    // e.g. https://graph.microsoft.com/v1.0/me/calendarView -> .../me/calendars
+    pub fn calendars_url(&self) -> String {
+        format!("{}/calendars", self.me_url())
+    }
+
+    // The configured select plus what every event needs to be listed at all.
+    pub fn select_query(&self) -> Option<String> {
+        if self.select.is_empty() {
+            return None;
+        }
+        // iCalUId tells copies of a meeting in several calendars apart from separate meetings.
+        let mut fields = vec!["id", "start", "end", "isAllDay", "isCancelled", "iCalUId"];
+        for field in &self.select {
+            if !fields.contains(&field.as_str()) {
+                fields.push(field);
+            }
+        }
+        Some(fields.join(","))
+    }
+
+    fn me_url(&self) -> &str {
+        self.base_url
+            .rsplit_once('/')
+            .map_or(self.base_url.as_str(), |(me, _)| me)
+    }
+}
+
+impl GoogleConfig {
+    pub fn redirect_uri(&self) -> String {
+        format!("http://localhost:{}/redirect", self.redirect_port)
+    }
 }
 
 impl Config {
-    pub fn from_path() -> Self {
-        let home = std::env::var_os("HOME").expect("ERROR: No HOME OS variable found!");
-        let config_path = CONFIG_PATH
-            .get()
-            .expect("ERROR: No config path resolved!")
-            .replace("$HOME", home.to_str().unwrap());
+    pub fn outlook_accounts(&self) -> Vec<Account<'_>> {
+        match self.accounts.is_empty() {
+            true => vec![Account {
+                name: None,
+                outlook: &self.outlook,
+            }],
+            false => self
+                .accounts
+                .iter()
+                .map(|account| Account {
+                    name: Some(account.name.as_str()),
+                    outlook: &account.outlook,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn outlook_account(&self, name: Option<&str>) -> Option<Account<'_>> {
+        self.outlook_accounts()
+            .into_iter()
+            .find(|account| account.name == name)
+    }
+
+    pub fn palette(&self) -> Option<usize> {
+        match self.theme {
+            Theme::Palette(index) => Some(index),
+            Theme::Custom(_) => None,
+        }
+    }
+
+    // NO_COLOR counts when set to anything but the empty string, as https://no-color.org asks.
+    pub fn no_color(&self) -> bool {
+        self.no_color || std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+    }
+
+    // Checked by validate, so only None when unset.
+    pub fn display_tz(&self) -> Option<Tz> {
+        self.display_timezone.as_ref()?.parse().ok()
+    }
+
+    pub fn notification_offsets(&self) -> Vec<i64> {
+        match (
+            &self.notification_offsets_minutes,
+            self.notification_period_minutes,
+        ) {
+            (offsets, _) if !offsets.is_empty() => offsets.clone(),
+            (_, Some(period)) => vec![period],
+            _ => vec![5],
+        }
+    }
+
+    pub fn from_path() -> Result<Self, ConfigError> {
+        let path = config_path();
+        let file = match std::fs::read_to_string(&path) {
+            Ok(file) => file,
+            // First run: leave a starting point behind instead of panicking.
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir).map_err(ConfigError::Write)?;
+                }
+                std::fs::write(&path, DEFAULT_CONFIG).map_err(ConfigError::Write)?;
+                return Err(ConfigError::Created(path));
+            }
+            Err(error) => return Err(ConfigError::Read(error)),
+        };
+        let mut config = toml::from_str::<Config>(&file).map_err(ConfigError::Parse)?;
+        // Older setups exported CLIENT_ID rather than setting it here; the config wins.
+        if config.outlook.client_id.is_empty() {
+            if let Ok(client_id) = std::env::var("CLIENT_ID") {
+                config.outlook.client_id = client_id;
+            }
+        }
+        config.validate()
+    }
+
+    // The user's settings if there are any, but with canned events and no sign-in.
+    pub fn demo() -> Result<Self, ConfigError> {
         let file =
-            std::fs::read_to_string(config_path).expect("ERROR: Could not read config file!");
-        toml::from_str(&file).unwrap()
+            std::fs::read_to_string(config_path()).unwrap_or_else(|_| DEFAULT_CONFIG.to_string());
+        let mut config = toml::from_str::<Config>(&file).map_err(ConfigError::Parse)?;
+        config.provider = Provider::Mock;
+        config.ics_urls.clear();
+        config.validate()
+    }
+
+    // Catches values that parse fine but would misbehave at runtime.
+    pub fn validate(self) -> Result<Self, ConfigError> {
+        let invalid = |field: &'static str, message: &str| {
+            Err(ConfigError::Invalid {
+                field,
+                message: message.to_string(),
+            })
+        };
+        let missing = |value: &str| value.trim().is_empty() || value == PLACEHOLDER_CLIENT_ID;
+
+        if self.refresh_period_seconds == 0 {
+            return invalid("refresh_period_seconds", "must be greater than 0");
+        }
+        if self.columns.is_empty() {
+            return invalid("columns", "must list at least one column");
+        }
+        if self.log_level.parse::<LevelFilter>().is_err() {
+            return invalid(
+                "log_level",
+                "must be off, error, warn, info, debug or trace",
+            );
+        }
+        if self.network.timeout_seconds == 0 {
+            return invalid("network.timeout_seconds", "must be greater than 0");
+        }
+        if let Some(proxy_url) = &self.network.proxy_url {
+            if let Err(error) = reqwest::Proxy::all(proxy_url) {
+                return invalid("network.proxy_url", &error.to_string());
+            }
+        }
+        if self.limit_days == 0 {
+            return invalid("limit_days", "must be greater than 0");
+        }
+        if let Some(zone) = &self.display_timezone {
+            if zone.parse::<Tz>().is_err() {
+                return invalid(
+                    "display_timezone",
+                    "must be an IANA name such as \"Europe/London\"",
+                );
+            }
+        }
+        if self.auth_timeout_millis == 0 {
+            return invalid("auth_timeout_millis", "must be greater than 0");
+        }
+        if self.snooze_minutes <= 0 {
+            return invalid("snooze_minutes", "must be greater than 0");
+        }
+        if self.popup_auto_dismiss_seconds == Some(0) {
+            return invalid("popup_auto_dismiss_seconds", "must be greater than 0");
+        }
+        if self.notification_offsets().iter().any(|offset| *offset < 0) {
+            return invalid("notification_offsets_minutes", "must not be negative");
+        }
+        if self
+            .notification_command
+            .as_ref()
+            .is_some_and(|command| command.is_empty())
+        {
+            return invalid("notification_command", "must name a program to run");
+        }
+
+        // Compared as their token files are named, so no two accounts share one.
+        let mut names = HashSet::new();
+        for account in &self.accounts {
+            if account.name.trim().is_empty() || !names.insert(auth::slug(&account.name)) {
+                return invalid(
+                    "accounts.name",
+                    "must be set and different for each account, besides punctuation",
+                );
+            }
+            if missing(&account.outlook.client_id) {
+                return invalid(
+                    "accounts.client_id",
+                    "set this to the Azure app's client ID for the account",
+                );
+            }
+            // One redirect server serves every sign-in.
+            if account.outlook.redirect_port != self.accounts[0].outlook.redirect_port {
+                return invalid(
+                    "accounts.redirect_port",
+                    "must be the same for every account",
+                );
+            }
+        }
+
+        match self.provider {
+            Provider::Outlook if self.accounts.is_empty() && missing(&self.outlook.client_id) => {
+                invalid(
+                    "outlook.client_id",
+                    "set this to your Azure app's client ID",
+                )
+            }
+            Provider::Google if missing(&self.google.client_id) => {
+                invalid("google.client_id", "set this to your OAuth client ID")
+            }
+            Provider::Google if self.google.client_secret.is_empty() => invalid(
+                "google.client_secret",
+                "set this to your OAuth client secret",
+            ),
+            Provider::CalDav if self.caldav.url.is_empty() => {
+                invalid("caldav.url", "set this to your calendar collection URL")
+            }
+            Provider::CalDav if self.caldav.password_command.is_empty() => invalid(
+                "caldav.password_command",
+                "set this to a command printing your password",
+            ),
+            Provider::Ics if self.ics_urls.is_empty() => {
+                invalid("ics_urls", "list at least one feed for the ics provider")
+            }
+            _ => Ok(self),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    // Not a failure as such: there was no config, so a default one was written.
+    Created(PathBuf),
+    Read(std::io::Error),
+    Write(std::io::Error),
+    Parse(toml::de::Error),
+    Invalid {
+        field: &'static str,
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = config_path();
+        match self {
+            ConfigError::Created(path) => write!(
+                f,
+                "Created a default config at {}.\nSet your client_id there and restart cal-tui.",
+                path.display()
+            ),
+            ConfigError::Read(error) => {
+                write!(f, "Could not read {}: {error}", path.display())
+            }
+            ConfigError::Write(error) => {
+                write!(f, "Could not create {}: {error}", path.display())
+            }
+            ConfigError::Parse(error) => write!(f, "Invalid {}: {error}", path.display()),
+            ConfigError::Invalid { field, message } => {
+                write!(f, "Invalid {} in {}: {message}", field, path.display())
+            }
+        }
+    }
+}
+
+static PLACEHOLDER_CLIENT_ID: &str = "YOUR_CLIENT_ID";
+
+const DEFAULT_CONFIG: &str = r##"# cal-tui configuration
+
+# Colour palette, 0-8. For custom colors, drop this line and add a [theme] table at the
+# end of the file giving buffer_bg, header_bg, header_fg, row_fg, selected_style_fg,
+# normal_row_color and alt_row_color as "#rrggbb" or color names. Press T to
+# try each palette in turn; the last one tried is kept until this line changes.
+theme = 0
+# Stripe rows in alternating colors. Turn off if the two are hard to tell apart.
+alternating_rows = true
+# How the selected row is marked: reversed, bold, or bg to fill it with
+# selected_style_fg.
+selection_style = "reversed"
+# Below this many columns and rows, a resize hint is shown instead of the UI.
+min_width = 40
+min_height = 10
+
+# Minutes before an event starts to raise an alert. One alert per entry.
+notification_offsets_minutes = [5]
+snooze_minutes = 5
+# Close alerts nobody has answered after this many seconds.
+# popup_auto_dismiss_seconds = 300
+# What alerts say. The first line titles desktop notifications. Placeholders:
+# {subject} {organizer} {location} {when} {start} {end} {duration} {minutes}
+# {calendar}
+alert_template = "{subject}\n{when}\n{organizer}"
+# Changes to a listed meeting that raise a notice with the old and new values.
+# Any of "start", "end" and "location"; [] turns the notices off.
+notify_changes = ["start", "end", "location"]
+
+# Open the link of accepted meetings when they start, after a countdown that
+# can be cancelled.
+auto_join = false
+auto_join_delay_seconds = 10
+
+# How often to poll the calendar, and how many days ahead to show.
+refresh_period_seconds = 60
+limit_days = 7
+
+# How long to wait for the browser sign-in to complete.
+auth_timeout_millis = 60000
+
+# "12h" or "24h".
+time_format = "24h"
+# Show times in this zone rather than the system's, e.g. when travelling.
+# display_timezone = "America/New_York"
+
+# Hide events marked as free time.
+hide_free_events = false
+
+# Draw with bold and reverse only, no colors. Also on when NO_COLOR is set.
+no_color = false
+
+# Press f for today's free/busy bar, which highlights open time at least this
+# many minutes long. It spans [working_hours] when set, otherwise the whole day.
+free_slot_minutes = 30
+
+# Table columns, in order: subject, start, end, duration, location, organizer,
+# response and account.
+columns = ["subject", "start", "duration"]
+
+# Ask before quitting.
+confirm_quit = false
+
+# Sound to play with alerts: a wav, mp3, ogg or flac file, or "bell".
+# alert_sound = "bell"
+
+# Diagnostics go to a file, never the screen: off, error, warn, info, debug or
+# trace. The file is rotated daily and defaults to cal-tui.log in this directory.
+log_level = "warn"
+# log_file = "/tmp/cal-tui.log"
+
+# outlook, google, caldav, ics, or mock for made-up events.
+provider = "outlook"
+
+# "browser", or "device_code" to sign in from another device over SSH.
+auth_flow = "browser"
+
+[outlook]
+# Application (client) ID of your Azure app registration.
+client_id = "YOUR_CLIENT_ID"
+redirect_port = 8000
+# Sign-in prompt: login, consent, select_account or none. "none" only works when
+# already signed in to Microsoft in the browser.
+prompt = "select_account"
+# Use Calendars.ReadWrite instead of Calendars.ReadBasic to accept and decline
+# meetings. Keep offline_access, or you'll have to sign in again every hour.
+scopes = ["openid", "Calendars.ReadBasic", "offline_access"]
+# Read another calendar than the default; press c to cycle through them.
+# calendar_id = "AAMkAGI2..."
+# Or show several calendars at once, each marked with its own color.
+# calendar_ids = ["AAMkAGI2...", "AAMkADc3..."]
+# Only these fields are fetched when polling, for large mailboxes or slow links.
+# The rest of an event is loaded when it is opened. Leave out onlineMeeting and
+# responseStatus and alerts can't offer to join, nor auto_join tell what you
+# accepted. Add e.g. "location" or "organizer" for those columns, or set it to
+# [] to fetch every field.
+# select = ["subject", "showAs", "isOnlineMeeting", "onlineMeeting", "responseStatus"]
+
+# Several Microsoft accounts, e.g. in different tenants, shown together in place
+# of [outlook]. Each takes the settings above plus a name, and signs in in turn.
+# Press p to show one account at a time.
+# [[accounts]]
+# name = "Work"
+# client_id = "YOUR_CLIENT_ID"
+#
+# [[accounts]]
+# name = "Client"
+# client_id = "ANOTHER_CLIENT_ID"
+# scopes = ["openid", "Calendars.Read", "offline_access"]
+
+# Proxy and timeouts for every request. Without proxy_url, HTTPS_PROXY and
+# HTTP_PROXY from the environment are used.
+# [network]
+# proxy_url = "http://proxy.example.com:8080"
+# timeout_seconds = 30
+# danger_accept_invalid_certs = false
+
+# Dim events entirely outside these hours, in display_timezone. Press H to hide
+# them instead.
+# [working_hours]
+# start = "09:00"
+# end = "17:30"
+
+# Order of the table: by start, end or subject, asc or desc. Events are grouped
+# under day headings unless sorted by subject.
+# [sort]
+# by = "start"
+# order = "asc"
+
+# Tag events with the color of their first matching category.
+# [category_colors]
+# "Red category" = "red"
+# Travel = "#f59e0b"
+"##;
+
+pub fn config_path() -> PathBuf {
+    CONFIG_PATH
+        .get()
+        .expect("ERROR: No config path resolved!")
+        .clone()
+}
+
+// Directory holding config.toml, also used for state such as cached tokens.
+pub fn config_dir() -> PathBuf {
+    config_path()
+        .parent()
+        .map(PathBuf::from)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        mock,
+        outlook::{tests::value, to_calendar_event},
+        CONFIG_PATH,
+    };
+
+    // The default config with canned events, and nothing carried over from a previous session.
+    pub(crate) fn config() -> &'static Config {
+        CONFIG_PATH.get_or_init(|| std::env::temp_dir().join("cal-tui-test/config.toml"));
+        CONFIG.get_or_init(|| {
+            let mut config = toml::from_str::<Config>(DEFAULT_CONFIG).unwrap();
+            config.provider = Provider::Mock;
+            // Times come out the same whatever the machine's zone.
+            config.display_timezone = Some("UTC".to_string());
+            config
+        })
+    }
+
+    pub(crate) fn app() -> App {
+        config();
+        App::new(AppBackend::new())
+    }
+
+    #[test]
+    fn account_names_must_not_share_a_token_file() {
+        let accounts = |first: &str, second: &str| {
+            toml::from_str::<Config>(&format!(
+                "{DEFAULT_CONFIG}
+[[accounts]]
+name = \"{first}\"
+client_id = \"one\"
+
+[[accounts]]
+name = \"{second}\"
+client_id = \"two\"
+"
+            ))
+            .unwrap()
+            .validate()
+        };
+
+        assert!(accounts("me@corp", "me@home").is_ok());
+        assert!(matches!(
+            accounts("me@corp", "me.corp"),
+            Err(ConfigError::Invalid {
+                field: "accounts.name",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn demo_sessions_leave_the_saved_state_alone() {
+        let mut app = app();
+        let path = config_dir().join("state.json");
+        _ = fs::remove_file(&path);
+        app.filter = "demo".to_string();
+        app.save_state();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn refreshes_keep_one_entry_per_event() {
+        let mut app = app();
+        let events = mock::events(Utc::now());
+        for _ in 0..3 {
+            for event in events.clone() {
+                app.add_event(event);
+            }
+            assert_eq!(app.events.len(), events.len());
+        }
+    }
+
+    #[test]
+    fn events_sharing_a_start_are_both_listed() {
+        let mut app = app();
+        let first = mock::events(Utc::now()).remove(0);
+        let second = CalendarEvent {
+            id: "other".to_string(),
+            ..first.clone()
+        };
+        assert_eq!(app.add_event(first), Some("mock-0".to_string()));
+        assert_eq!(app.add_event(second), Some("other".to_string()));
+        assert_eq!(app.events_by_start().len(), 2);
+    }
+
+    #[test]
+    fn overlapping_timers_alert_for_their_own_events() {
+        let mut app = app();
+        let events = mock::events(Utc::now());
+        // The stand-up and the design review overlap.
+        for event in events.iter().take(2).cloned() {
+            app.add_event(event);
+        }
+        app.schedule_alert(20, "mock-1".to_string(), events[1].start_time, 5);
+        app.schedule_alert(0, "mock-0".to_string(), events[0].start_time, 5);
+
+        let fired = (0..2)
+            .map(|_| app.backend.timer_rx.recv().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fired,
+            [
+                ("mock-0".to_string(), events[0].start_time, 5),
+                ("mock-1".to_string(), events[1].start_time, 5)
+            ]
+        );
+        for (id, start_time, _) in fired {
+            assert!(app.is_scheduled(&id, start_time));
+            app.alert_id = Some(id.clone());
+            assert_eq!(app.alert_event().map(|event| &event.id), Some(&id));
+        }
+    }
+
+    #[test]
+    fn typing_a_prefix_jumps_to_the_matching_subject() {
+        let mut app = app();
+        app.focus = Focus::Table;
+        let mut events = mock::events(Utc::now());
+        let standup = CalendarEvent {
+            id: "standup".to_string(),
+            subject: "Standup".to_string(),
+            ..events[9].clone()
+        };
+        events.push(standup);
+        for event in events {
+            app.add_event(event);
+        }
+        let press = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+        // s snoozes and t jumps to now elsewhere, but here they spell out a prefix.
+        app.handle_key(press('s'));
+        app.handle_key(press('t'));
+        assert_eq!(app.selected_event().unwrap().subject, "Standup");
+
+        // Once the prefix has lapsed, t is back to jumping to now.
+        app.type_ahead_at -= TYPE_AHEAD_TIMEOUT * 2;
+        app.handle_key(press('t'));
+        assert_eq!(app.selected_event().unwrap().subject, "Daily stand-up");
+    }
+
+    #[test]
+    fn timers_from_before_a_reschedule_are_ignored() {
+        let mut app = app();
+        let event = mock::events(Utc::now()).remove(0);
+        let start_time = event.start_time;
+        app.add_event(event.clone());
+        app.schedule_alert(0, event.id.clone(), start_time, 5);
+
+        let moved = CalendarEvent {
+            start_time: start_time + chrono::Duration::hours(1),
+            end_time: event.end_time + chrono::Duration::hours(1),
+            ..event
+        };
+        assert_eq!(app.add_event(moved), Some("mock-0".to_string()));
+
+        let (id, scheduled_for, _) = app.backend.timer_rx.recv().unwrap();
+        assert!(!app.is_scheduled(&id, scheduled_for));
+        assert!(app.is_scheduled(&id, start_time + chrono::Duration::hours(1)));
+        // Nor does the old start auto-join.
+        app.queue_join(id, scheduled_for);
+        assert!(app.pending_join.is_none());
+    }
+
+    #[test]
+    fn copies_from_other_calendars_share_a_row() {
+        let mut app = app();
+        let now = Utc::now().format("%Y-%m-%dT%H:%M:%S").to_string();
+        let start = json!({ "dateTime": now, "timeZone": "UTC" });
+        let copy = |id: &str, calendar: &str, location: Option<&str>| CalendarEvent {
+            calendar_name: Some(calendar.to_string()),
+            ..to_calendar_event(&value(json!({
+                "id": id,
+                "start": start,
+                "end": start,
+                "location": { "displayName": location },
+            })))
+            .unwrap()
+        };
+        app.add_event(copy("invite", "Calendar", Some("Room 4.02")));
+        app.add_event(copy("team", "Team", None));
+
+        assert_eq!(app.events.len(), 1);
+        assert_eq!(app.events["invite"].also_in, ["Team"]);
+
+        // Listed until the last copy goes, falling back on the one left.
+        app.remove_event("invite");
+        assert_eq!(app.events.len(), 1);
+        assert!(app.events["team"].also_in.is_empty());
+        app.remove_event("team");
+        assert!(app.events.is_empty());
+    }
+
+    #[test]
+    fn rescheduling_past_the_horizon_drops_the_event() {
+        let mut app = app();
+        let event = mock::events(Utc::now()).remove(0);
+        app.add_event(event.clone());
+
+        let limit_days = CONFIG.get().unwrap().limit_days as i64;
+        let moved = CalendarEvent {
+            start_time: event.start_time + chrono::Duration::days(limit_days + 1),
+            end_time: event.end_time + chrono::Duration::days(limit_days + 1),
+            ..event
+        };
+        assert_eq!(app.add_event(moved), None);
+        assert!(app.events.is_empty());
     }
 }
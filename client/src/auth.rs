@@ -1,98 +1,378 @@
-use std::{collections::HashMap, sync::mpsc::Sender};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::{mpsc::Sender, Arc, RwLock},
+    time::Duration,
+};
 
-use graph_oauth::oauth::{AccessToken, IdToken, OAuth};
-use warp::Filter;
+use chrono::{DateTime, Utc};
+use graph_oauth::oauth::{AccessToken, OAuth};
+use serde::Deserialize;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+use warp::{http::StatusCode, Filter};
 
-use crate::CONFIG;
+use crate::{
+    app::{config_dir, Account, OutlookConfig},
+    backend::http_client,
+    provider::Provider,
+};
 
-pub fn oauth_open_id() -> OAuth {
+static DEVICE_CODE_URL: &str = "https://login.microsoftonline.com/common/oauth2/v2.0/devicecode";
+static TOKEN_URL: &str = "https://login.microsoftonline.com/common/oauth2/v2.0/token";
+
+pub fn oauth_open_id(outlook: &OutlookConfig) -> OAuth {
+    let session = SESSION.read().unwrap();
     let mut oauth = OAuth::new();
     oauth
-        .client_id(CONFIG.get().unwrap().outlook.client_id.as_str())
+        .client_id(outlook.client_id.as_str())
         .authorize_url("https://login.microsoftonline.com/common/oauth2/v2.0/authorize")
-        .redirect_uri("http://localhost:8000/redirect")
-        .access_token_url("https://login.microsoftonline.com/common/oauth2/v2.0/token")
-        .refresh_token_url("https://login.microsoftonline.com/common/oauth2/v2.0/token")
+        .redirect_uri(outlook.redirect_uri().as_str())
+        .access_token_url(TOKEN_URL)
+        .refresh_token_url(TOKEN_URL)
         .response_type("id_token code")
         .response_mode("form_post")
-        .add_scope("openid")
-        .add_scope("Calendars.ReadBasic")
-        .add_scope("offline_access")
-        .nonce("7362CAEA-9CA5")
-        .prompt("none")
-        .state("12345");
+        .nonce(session.nonce.as_str())
+        .state(session.state.as_str());
+    for scope in &outlook.scopes {
+        oauth.add_scope(scope.as_str());
+    }
+    // "none" fails outright unless the user is already signed in, so it only suits
+    // re-authenticating an existing session.
+    if !outlook.prompt.is_empty() {
+        oauth.prompt(outlook.prompt.as_str());
+    }
     oauth
 }
 
+// Per sign-in values the redirect must echo back, guarding against forged or replayed logins,
+// and the account being signed in to.
+struct Session {
+    state: String,
+    nonce: String,
+    outlook: Option<&'static OutlookConfig>,
+}
+
+static SESSION: RwLock<Session> = RwLock::new(Session {
+    state: String::new(),
+    nonce: String::new(),
+    outlook: None,
+});
+
 pub async fn handle_redirect(
-    id_token: IdToken,
-    tx: Sender<String>,
+    form: HashMap<String, String>,
+    tx: Sender<AccessToken>,
 ) -> Result<Box<dyn warp::Reply>, warp::Rejection> {
-    // println!("Received IdToken: {id_token:#?}");
+    // The state is taken once it matches, so a replayed redirect finds nothing to match.
+    let matched = {
+        let mut session = SESSION.write().unwrap();
+        match (form.get("code"), form.get("state"), session.outlook) {
+            (Some(code), Some(state), Some(outlook))
+                if !session.state.is_empty() && *state == session.state =>
+            {
+                std::mem::take(&mut session.state);
+                Some((code, outlook))
+            }
+            _ => None,
+        }
+    };
+    let Some((code, outlook)) = matched else {
+        return Ok(Box::new(warp::reply::with_status(
+            "Sign-in rejected: the response did not match this session.",
+            StatusCode::BAD_REQUEST,
+        )));
+    };
 
-    let mut oauth = oauth_open_id();
+    // Redeem the authorization code for an access token.
+    let grant = [
+        ("grant_type", "authorization_code"),
+        ("code", code.as_str()),
+    ];
+    match request_token(outlook, &grant).await {
+        Ok(access_token) => {
+            info!("signed in to Outlook");
 
-    // Pass the id token to the oauth client.
-    oauth.id_token(id_token);
-
-    // Build the request to get an access token using open id connect.
-    let mut request = oauth.build_async().open_id_connect();
-
-    // Request an access token.
-    let response = request.access_token().send().await.unwrap();
-    // println!("{response:#?}");
-
-    if response.status().is_success() {
-        let access_token: AccessToken = response.json().await.unwrap();
-
-        // You can optionally pass the access token to the oauth client in order
-        // to use a refresh token to get more access tokens. The refresh token
-        // is stored in AccessToken.
-        let bearer_token = access_token.bearer_token();
-        tx.send(bearer_token.to_string())
-            .expect("ERROR: Could not send token between threads!");
-        oauth.access_token(access_token);
-
-        // If all went well here we can print out the OAuth config with the Access Token.
-        // println!("OAuth:\n{:#?}\n", &oauth);
-    } else {
-        // See if Microsoft Graph returned an error in the Response body
-        // let result: reqwest::Result<serde::Value> = response.json().await;
-        // println!("{result:#?}");
+            // The whole AccessToken is forwarded (rather than just the bearer) so the
+            // refresh token it carries can be used to renew access before expiry.
+            if tx.send(access_token).is_err() {
+                warn!("sign-in finished after the app stopped waiting for it");
+            }
+            Ok(Box::new(
+                "Successfully Logged In! You can close your browser.",
+            ))
+        }
+        Err(error) => {
+            error!(error, "access token request failed");
+            Ok(Box::new(warp::reply::with_status(
+                "Sign-in failed: the access token could not be obtained.",
+                StatusCode::BAD_GATEWAY,
+            )))
+        }
     }
+}
 
-    // Generic login page response.
-    Ok(Box::new(
-        "Successfully Logged In! You can close your browser.",
-    ))
+pub fn open_browser_authorization(outlook: &'static OutlookConfig) {
+    *SESSION.write().unwrap() = Session {
+        state: Uuid::new_v4().to_string(),
+        nonce: Uuid::new_v4().to_string(),
+        outlook: Some(outlook),
+    };
+
+    // Get the oauth client and request a browser sign in. A failure here surfaces
+    // as an authentication timeout rather than a panic.
+    let mut oauth = oauth_open_id(outlook);
+    let mut request = oauth.build_async().open_id_connect();
+    _ = request.browser_authorization().open();
 }
 
-pub async fn start_auth_server(tx: Sender<String>) {
+pub async fn start_auth_server(tx: Sender<AccessToken>, outlook: &'static OutlookConfig) {
     let cors = warp::cors().allow_any_origin();
 
     let routes = warp::post()
         .and(warp::path("redirect"))
         .and(warp::body::form())
-        .map(|simple_map: HashMap<String, String>| {
-            IdToken::new(
-                simple_map.get("id_token").expect("No id_token returned"),
-                simple_map.get("code").expect("No code returned"),
-                simple_map.get("state").expect("No state returned"),
-                simple_map
-                    .get("session_state")
-                    .expect("No session_state returned"),
-            )
-        })
-        .and_then(move |id_token| {
+        .and_then(move |form: HashMap<String, String>| {
             let tx = tx.clone();
-            handle_redirect(id_token, tx)
+            handle_redirect(form, tx)
         })
         .with(cors);
 
-    // Get the oauth client and request a browser sign in.
-    let mut oauth = oauth_open_id();
-    let mut request = oauth.build_async().open_id_connect();
-    request.browser_authorization().open().unwrap();
+    open_browser_authorization(outlook);
+
+    // Bind the same port the redirect_uri points at so the flow can complete. Every account
+    // shares it.
+    warp::serve(routes)
+        .run(([127, 0, 0, 1], outlook.redirect_port))
+        .await;
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceCode {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub expires_in: i64,
+    pub interval: u64,
+    #[serde(skip, default = "Utc::now")]
+    pub requested_at: DateTime<Utc>,
+}
+
+impl DeviceCode {
+    pub fn is_expired(&self) -> bool {
+        Utc::now() >= self.requested_at + chrono::Duration::seconds(self.expires_in)
+    }
+}
+
+#[derive(Deserialize)]
+struct DeviceCodeError {
+    error: String,
+}
+
+// Starts a sign-in that completes on any other device, for sessions without a local browser.
+pub async fn request_device_code(outlook: &OutlookConfig) -> Option<DeviceCode> {
+    let response = http_client()
+        .post(DEVICE_CODE_URL)
+        .form(&[
+            ("client_id", outlook.client_id.as_str()),
+            ("scope", outlook.scopes.join(" ").as_str()),
+        ])
+        .send()
+        .await
+        .ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    response.json::<DeviceCode>().await.ok()
+}
+
+// Polls until the user enters the code, then hands the token over like the redirect does.
+pub async fn poll_device_code(
+    device_code: DeviceCode,
+    tx: Sender<AccessToken>,
+    outlook: &OutlookConfig,
+) {
+    let client = http_client();
+    let mut interval = device_code.interval.max(1);
+
+    while !device_code.is_expired() {
+        sleep(Duration::from_secs(interval)).await;
+
+        let response = client
+            .post(TOKEN_URL)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                ("client_id", outlook.client_id.as_str()),
+                ("device_code", device_code.device_code.as_str()),
+            ])
+            .send()
+            .await;
+        let Ok(response) = response else {
+            continue;
+        };
+
+        if response.status().is_success() {
+            if let Ok(access_token) = response.json::<AccessToken>().await {
+                _ = tx.send(access_token);
+            }
+            return;
+        }
 
-    warp::serve(routes).run(([127, 0, 0, 1], 8000)).await;
+        match response.json::<DeviceCodeError>().await {
+            Ok(error) if error.error == "authorization_pending" => (),
+            Ok(error) if error.error == "slow_down" => interval += 5,
+            // Declined, expired or otherwise unusable; a retry requests a new code.
+            _ => return,
+        }
+    }
+}
+
+// Token requests go through the shared client rather than graph-oauth's own, so they reuse its
+// connections and honour the proxy and timeout in [network].
+async fn request_token(
+    outlook: &OutlookConfig,
+    grant: &[(&str, &str)],
+) -> Result<AccessToken, String> {
+    let scope = outlook.scopes.join(" ");
+    let redirect_uri = outlook.redirect_uri();
+    let mut form = vec![
+        ("client_id", outlook.client_id.as_str()),
+        ("scope", scope.as_str()),
+        ("redirect_uri", redirect_uri.as_str()),
+    ];
+    form.extend_from_slice(grant);
+
+    let response = http_client()
+        .post(TOKEN_URL)
+        .form(&form)
+        .send()
+        .await
+        .map_err(|error| error.to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        // Microsoft explains the failure in the body.
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("{status}: {body}"));
+    }
+    response
+        .json::<AccessToken>()
+        .await
+        .map_err(|error| error.to_string())
+}
+
+// Exchanges the refresh token for a new bearer, keeping the old refresh token when Microsoft
+// doesn't rotate it.
+pub async fn refresh_token(
+    access_token: &AccessToken,
+    outlook: &OutlookConfig,
+) -> Option<AccessToken> {
+    let refresh_token = access_token.refresh_token()?;
+    let grant = [
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token.as_str()),
+    ];
+    let mut refreshed = request_token(outlook, &grant).await.ok()?;
+    if refreshed.refresh_token().is_none() {
+        if let Some(refresh_token) = access_token.refresh_token() {
+            refreshed.set_refresh_token(refresh_token.as_str());
+        }
+    }
+    Some(refreshed)
+}
+
+pub async fn refresh_access_token(
+    mut access_token: AccessToken,
+    token: Arc<RwLock<String>>,
+    account: Account<'static>,
+) {
+    let mut expires_in = access_token.expires_in();
+
+    loop {
+        // Wake up a minute before the current bearer expires.
+        sleep(Duration::from_secs((expires_in - 60).max(0) as u64)).await;
+
+        match refresh_token(&access_token, account.outlook).await {
+            Some(refreshed) => {
+                info!(account = account.name, "refreshed the Outlook access token");
+                *token.write().unwrap() = refreshed.bearer_token().to_string();
+                save_token(Provider::Outlook, account.name, &refreshed);
+                expires_in = refreshed.expires_in();
+                access_token = refreshed;
+            }
+            // Retry shortly rather than letting the bearer lapse.
+            None => {
+                warn!(
+                    account = account.name,
+                    "could not refresh the Outlook access token, retrying"
+                );
+                expires_in = 70;
+            }
+        }
+    }
+}
+
+// One file per [[accounts]] entry, so each keeps its own sign-in.
+fn token_path(provider: Provider, account: Option<&str>) -> PathBuf {
+    let file = match (provider, account) {
+        (Provider::Google, _) => "google-token.json".to_string(),
+        (_, Some(name)) => format!("token-{}.json", slug(name)),
+        (_, None) => "token.json".to_string(),
+    };
+    config_dir().join(file)
+}
+
+// Account names come from the config, so anything that could leave the config directory or
+// trip up the filesystem is replaced.
+pub(crate) fn slug(name: &str) -> String {
+    name.chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect()
+}
+
+pub fn load_token(provider: Provider, account: Option<&str>) -> Option<AccessToken> {
+    let file = fs::read_to_string(token_path(provider, account)).ok()?;
+    serde_json::from_str(&file).ok()
+}
+
+// Cached tokens are credentials, so only the owner may read them.
+pub fn save_token(provider: Provider, account: Option<&str>, access_token: &AccessToken) {
+    let Ok(json) = serde_json::to_string(access_token) else {
+        return;
+    };
+    let path = token_path(provider, account);
+    if let Some(dir) = path.parent() {
+        _ = fs::create_dir_all(dir);
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    if let Ok(mut file) = options.open(path) {
+        _ = file.write_all(json.as_bytes());
+    }
+}
+
+pub fn clear_token(provider: Provider, account: Option<&str>) {
+    _ = fs::remove_file(token_path(provider, account));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slug_keeps_token_files_in_one_directory() {
+        assert_eq!(slug("work"), "work");
+        assert_eq!(slug("../work/me@corp.com"), "___work_me_corp_com");
+    }
 }
@@ -1,23 +1,199 @@
 use crate::{
-    auth::start_auth_server,
-    outlook::{refresh, CalendarEvent},
+    app::{Account, AuthFlow, Config, OutlookConfig},
+    auth::{self, DeviceCode},
+    caldav,
+    caldav::CalDavProvider,
+    google,
+    google::GoogleProvider,
+    ics::IcsProvider,
+    mock::MockProvider,
+    outlook::{self, Calendar, CalendarEvent, EventCommand, OutlookProvider},
+    provider::{refresh, CalendarProvider, Provider},
     CONFIG,
 };
+use chrono::{DateTime, Utc};
+use graph_oauth::oauth::AccessToken;
 use reqwest::Client;
 use std::{
-    sync::mpsc::{channel, Receiver, Sender},
-    time::Duration,
+    collections::HashMap,
+    fmt,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex, Once, OnceLock, RwLock,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    runtime::{self, Runtime},
+    sync::watch,
 };
-use tokio::runtime::{self, Runtime};
 
 pub struct Backend {
     pub auth: Runtime,
     pub data: Runtime,
     pub timer: Runtime,
-    pub event_tx: Sender<CalendarEvent>,
-    pub event_rx: Receiver<CalendarEvent>,
-    pub timer_tx: Sender<()>,
-    pub timer_rx: Receiver<()>,
+    pub event_tx: Sender<EventCommand>,
+    pub event_rx: Receiver<EventCommand>,
+    // ID and start of the event an alert is for, with the minutes before it starts.
+    pub timer_tx: Sender<(String, DateTime<Utc>, i64)>,
+    pub timer_rx: Receiver<(String, DateTime<Utc>, i64)>,
+    // ID and start of an event that has just started, for auto_join.
+    pub join_tx: Sender<(String, DateTime<Utc>)>,
+    pub join_rx: Receiver<(String, DateTime<Utc>)>,
+    pub auth_tx: Sender<AccessToken>,
+    pub auth_rx: Receiver<AccessToken>,
+    pub auth_server: Once,
+    pub action_tx: Sender<Result<String, String>>,
+    pub action_rx: Receiver<Result<String, String>>,
+    // Full events fetched when a row is opened, while polls only $select a few fields.
+    pub detail_tx: Sender<CalendarEvent>,
+    pub detail_rx: Receiver<CalendarEvent>,
+    // Bearers by [[accounts]] name; None for a single account.
+    pub tokens: RwLock<HashMap<Option<String>, Arc<RwLock<String>>>>,
+    pub status_tx: Sender<AppStatus>,
+    pub status_rx: Receiver<AppStatus>,
+    pub error_tx: Sender<BackendError>,
+    pub error_rx: Receiver<BackendError>,
+    // A signal asking the app to exit, see listen_for_signals.
+    pub quit_tx: Sender<()>,
+    pub quit_rx: Receiver<()>,
+    // Device code sign-in waiting on the user, kept across retries.
+    pub device_code: Mutex<DeviceCodeSignIn>,
+    // The code requested for it, or None when the request failed.
+    pub device_code_tx: Sender<Option<DeviceCode>>,
+    pub device_code_rx: Receiver<Option<DeviceCode>>,
+    // When the browser was opened for the sign-in still being waited on.
+    pub browser_sign_in: Mutex<Option<Instant>>,
+    // Outlook accounts already signed in and polling, in config order.
+    pub accounts_started: Mutex<usize>,
+    // Outlook calendars the account can see, and the one being read (None for the default).
+    pub calendars: Arc<RwLock<Vec<Calendar>>>,
+    pub calendar_id: Arc<RwLock<Option<String>>>,
+    // Wakes every refresh loop for an immediate poll.
+    pub refresh_tx: watch::Sender<()>,
+}
+
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+// One client for every request, so all of them go through the configured proxy and share a
+// connection pool. Clones are cheap handles onto it.
+pub fn http_client() -> Client {
+    CLIENT
+        .get_or_init(|| {
+            let network = &CONFIG.get().unwrap().network;
+            let mut builder = Client::builder()
+                .timeout(Duration::from_secs(network.timeout_seconds))
+                .danger_accept_invalid_certs(network.danger_accept_invalid_certs);
+            // Checked when the config was loaded.
+            if let Some(proxy) = network
+                .proxy_url
+                .as_deref()
+                .and_then(|url| reqwest::Proxy::all(url).ok())
+            {
+                builder = builder.proxy(proxy);
+            }
+            builder
+                .build()
+                .expect("ERROR: Could not build the HTTP client!")
+        })
+        .clone()
+}
+
+// What the backend is busy with, so startup isn't just an empty table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppStatus {
+    Authenticating,
+    Fetching,
+    Ready,
+    Error(String),
+}
+
+// How far device code sign-in has got.
+pub enum DeviceCodeSignIn {
+    Idle,
+    Requesting,
+    Waiting(DeviceCode),
+}
+
+#[derive(Debug, Clone)]
+pub enum AuthError {
+    Timeout,
+    PasswordCommand(String),
+    // Not failures as such: the user still has to finish signing in elsewhere.
+    Browser,
+    RequestingDeviceCode,
+    DeviceCode(DeviceCode),
+    DeviceCodeRequest,
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthError::Timeout => write!(f, "Timed out waiting for sign-in"),
+            AuthError::PasswordCommand(error) => write!(f, "Password command failed: {error}"),
+            AuthError::Browser => write!(f, "Finish signing in in the browser window that opened"),
+            AuthError::RequestingDeviceCode => write!(f, "Requesting a sign-in code..."),
+            AuthError::DeviceCode(device_code) => write!(
+                f,
+                "Go to {} and enter the code {}",
+                device_code.verification_uri, device_code.user_code
+            ),
+            AuthError::DeviceCodeRequest => write!(f, "Could not request a sign-in code"),
+        }
+    }
+}
+
+// Non-fatal problems from the refresh threads, shown in the status line.
+#[derive(Debug, Clone)]
+pub enum BackendError {
+    MalformedEvent {
+        subject: Option<String>,
+        reason: String,
+    },
+    FetchFailed {
+        failures: u32,
+        reason: String,
+    },
+    // The token was rejected and could not be renewed.
+    SignInExpired,
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackendError::MalformedEvent {
+                subject: Some(subject),
+                reason,
+            } => write!(f, "Skipped event \"{subject}\": {reason}"),
+            BackendError::MalformedEvent {
+                subject: None,
+                reason,
+            } => write!(f, "Skipped an event: {reason}"),
+            BackendError::FetchFailed {
+                failures: 1,
+                reason,
+            } => {
+                write!(
+                    f,
+                    "Events may be out of date: the last refresh failed ({reason})"
+                )
+            }
+            BackendError::FetchFailed { failures, reason } => write!(
+                f,
+                "Events may be out of date: {failures} refreshes failed ({reason})"
+            ),
+            BackendError::SignInExpired => write!(
+                f,
+                "Events are no longer updating: the sign-in expired, restart to sign in again"
+            ),
+        }
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Backend {
@@ -45,6 +221,14 @@ impl Backend {
 
         let (event_tx, event_rx) = channel();
         let (timer_tx, timer_rx) = channel();
+        let (join_tx, join_rx) = channel();
+        let (auth_tx, auth_rx) = channel();
+        let (action_tx, action_rx) = channel();
+        let (detail_tx, detail_rx) = channel();
+        let (status_tx, status_rx) = channel();
+        let (error_tx, error_rx) = channel();
+        let (device_code_tx, device_code_rx) = channel();
+        let (quit_tx, quit_rx) = channel();
 
         Self {
             auth,
@@ -54,23 +238,384 @@ impl Backend {
             event_rx,
             timer_tx,
             timer_rx,
+            join_tx,
+            join_rx,
+            auth_tx,
+            auth_rx,
+            auth_server: Once::new(),
+            action_tx,
+            action_rx,
+            detail_tx,
+            detail_rx,
+            tokens: RwLock::new(HashMap::new()),
+            status_tx,
+            status_rx,
+            error_tx,
+            error_rx,
+            quit_tx,
+            quit_rx,
+            device_code: Mutex::new(DeviceCodeSignIn::Idle),
+            device_code_tx,
+            device_code_rx,
+            browser_sign_in: Mutex::new(None),
+            accounts_started: Mutex::new(0),
+            refresh_tx: watch::channel(()).0,
+            calendars: Arc::new(RwLock::new(Vec::new())),
+            calendar_id: Arc::new(RwLock::new(
+                CONFIG.get().unwrap().outlook.calendar_id.clone(),
+            )),
         }
     }
 
-    pub fn start(&self) {
-        // Auth thread
-        let (auth_tx, auth_rx) = channel();
-        self.auth
-            .spawn(async move { start_auth_server(auth_tx).await });
-        let token = auth_rx
-            .recv_timeout(Duration::from_millis(
-                CONFIG.get().unwrap().auth_timeout_millis,
-            ))
-            .expect("ERROR: Unsuccessful authentication!");
+    // Closing the terminal or `kill` would end the process in raw mode on the alternate screen,
+    // so those signals are turned into a quit instead. Only for the TUI: the agenda commands
+    // keep the default handling.
+    pub fn listen_for_signals(&self) {
+        let quit_tx = self.quit_tx.clone();
+        self.timer.spawn(async move {
+            wait_for_signal().await;
+            _ = quit_tx.send(());
+        });
+    }
+
+    // Cancels the polling loops and the auth server rather than leaving them to die with the
+    // process, so requests aren't cut off mid-write and the redirect port is free for a restart.
+    pub fn shutdown(self) {
+        for runtime in [self.auth, self.data, self.timer] {
+            runtime.shutdown_timeout(Duration::from_millis(500));
+        }
+    }
+
+    pub fn start(&self) -> Result<(), AuthError> {
+        _ = self.status_tx.send(AppStatus::Authenticating);
+        let result = self.spawn_providers();
+        _ = self.status_tx.send(match &result {
+            Ok(_) => AppStatus::Fetching,
+            Err(
+                AuthError::Browser | AuthError::RequestingDeviceCode | AuthError::DeviceCode(_),
+            ) => AppStatus::Authenticating,
+            Err(error) => AppStatus::Error(error.to_string()),
+        });
+        result
+    }
+
+    fn spawn_providers(&self) -> Result<(), AuthError> {
+        let config = CONFIG.get().unwrap();
 
         // Start data refresh thread
+        match config.provider {
+            Provider::Outlook => {
+                // Accounts sign in one after another; a retry carries on from the first one that
+                // hasn't finished.
+                let mut started = self.accounts_started.lock().unwrap();
+                for account in config.outlook_accounts().into_iter().skip(*started) {
+                    let token = self.authenticate(config, Some(account))?;
+                    self.spawn_outlook(config, account, token);
+                    *started += 1;
+                }
+            }
+            Provider::Google => {
+                let provider = GoogleProvider {
+                    token: self.authenticate(config, None)?,
+                    client: http_client(),
+                    calendar_id: config.google.calendar_id.clone(),
+                };
+                self.spawn_provider(provider);
+            }
+            Provider::CalDav => {
+                let provider = CalDavProvider {
+                    client: http_client(),
+                    url: config.caldav.url.clone(),
+                    username: config.caldav.username.clone(),
+                    password: caldav::password(&config.caldav.password_command)?,
+                };
+                self.spawn_provider(provider);
+            }
+            Provider::Mock => {
+                let provider = MockProvider::from_now();
+                self.spawn_provider(provider);
+            }
+            Provider::Ics => (),
+        }
+
+        // ICS feeds need no sign-in and are polled alongside any account.
+        if !config.ics_urls.is_empty() {
+            let provider = IcsProvider {
+                client: http_client(),
+                urls: config.ics_urls.clone(),
+            };
+            self.spawn_provider(provider);
+        }
+
+        Ok(())
+    }
+
+    // Polls any source on the refresh schedule, feeding the same channels as the built-in ones.
+    pub fn spawn_provider<P: CalendarProvider + Send + Sync + 'static>(&self, provider: P) {
+        let config = CONFIG.get().unwrap();
         let event_tx = self.event_tx.clone();
-        self.data
-            .spawn(async move { refresh(token, Client::new(), event_tx).await });
+        let status_tx = self.status_tx.clone();
+        let error_tx = self.error_tx.clone();
+        let refresh_rx = self.refresh_tx.subscribe();
+        self.data.spawn(async move {
+            refresh(provider, event_tx, status_tx, error_tx, refresh_rx, config).await
+        });
+    }
+
+    // Polls one Outlook account, or each of its calendar_ids.
+    fn spawn_outlook(
+        &self,
+        config: &'static Config,
+        account: Account<'static>,
+        token: Arc<RwLock<String>>,
+    ) {
+        // Only the lone [outlook] account can switch calendars from the UI.
+        let calendar_id = match account.name {
+            None => self.calendar_id.clone(),
+            Some(_) => Arc::new(RwLock::new(account.outlook.calendar_id.clone())),
+        };
+        let calendars = self.calendars.clone();
+        let event_tx = self.event_tx.clone();
+        let status_tx = self.status_tx.clone();
+        let error_tx = self.error_tx.clone();
+        let refresh_rx = self.refresh_tx.subscribe();
+        self.data.spawn(async move {
+            // Needed for cycling between calendars and for labelling merged ones. A
+            // failure here is harmless; merged calendars are labelled by ID instead.
+            let bearer = token.read().unwrap().clone();
+            let calendars_url = account.outlook.calendars_url();
+            let list = outlook::list_calendars(&http_client(), &calendars_url, &bearer)
+                .await
+                .unwrap_or_default();
+            if account.name.is_none() {
+                *calendars.write().unwrap() = list.clone();
+            }
+
+            let provider = |calendar_id, calendar_name| OutlookProvider {
+                token: token.clone(),
+                outlook: account.outlook,
+                client: http_client(),
+                base_url: account.outlook.base_url.clone(),
+                error_tx: error_tx.clone(),
+                delta: Mutex::new(None),
+                calendar_id,
+                calendar_name,
+                account: account.name.map(str::to_string),
+            };
+
+            if account.outlook.calendar_ids.is_empty() {
+                return refresh(
+                    provider(calendar_id, None),
+                    event_tx,
+                    status_tx,
+                    error_tx.clone(),
+                    refresh_rx,
+                    config,
+                )
+                .await;
+            }
+
+            // One poll per calendar, all feeding the same table.
+            for id in &account.outlook.calendar_ids {
+                let name = list
+                    .iter()
+                    .find(|calendar| calendar.id == *id)
+                    .map_or(id.clone(), |calendar| calendar.name.clone());
+                let provider = provider(Arc::new(RwLock::new(Some(id.clone()))), Some(name));
+                tokio::spawn(refresh(
+                    provider,
+                    event_tx.clone(),
+                    status_tx.clone(),
+                    error_tx.clone(),
+                    refresh_rx.clone(),
+                    config,
+                ));
+            }
+        });
+    }
+
+    // The bearer for an account, once it has signed in. Shared with its token refresh thread.
+    pub fn token(&self, account: Option<&str>) -> Option<Arc<RwLock<String>>> {
+        self.tokens
+            .read()
+            .unwrap()
+            .get(&account.map(str::to_string))
+            .cloned()
+    }
+
+    // account is None for every provider but Outlook.
+    fn authenticate(
+        &self,
+        config: &'static Config,
+        account: Option<Account<'static>>,
+    ) -> Result<Arc<RwLock<String>>, AuthError> {
+        let name = account.and_then(|account| account.name);
+        // A cached token skips the browser entirely as long as it can still be refreshed.
+        let access_token = match self.auth.block_on(cached_token(config.provider, account)) {
+            Some(access_token) => access_token,
+            None => self.sign_in(config, account)?,
+        };
+        auth::save_token(config.provider, name, &access_token);
+
+        // Token refresh thread. The bearer is shared so UI actions can reuse it.
+        let token = Arc::new(RwLock::new(access_token.bearer_token().to_string()));
+        self.tokens
+            .write()
+            .unwrap()
+            .insert(name.map(str::to_string), token.clone());
+        let refresh_token = token.clone();
+        match (config.provider, account) {
+            (Provider::Outlook, Some(account)) => {
+                self.auth.spawn(async move {
+                    auth::refresh_access_token(access_token, refresh_token, account).await
+                });
+            }
+            (Provider::Google, _) => {
+                self.auth.spawn(async move {
+                    google::refresh_access_token(access_token, refresh_token).await
+                });
+            }
+            _ => (),
+        };
+
+        Ok(token)
+    }
+
+    fn sign_in(
+        &self,
+        config: &'static Config,
+        account: Option<Account<'static>>,
+    ) -> Result<AccessToken, AuthError> {
+        if let (AuthFlow::DeviceCode, Some(account)) = (config.auth_flow, account) {
+            return self.device_code_sign_in(account.outlook);
+        }
+
+        // Like the device code flow this never blocks; each retry checks on the redirect, and
+        // gives up once auth_timeout_millis has passed.
+        let mut started = self.browser_sign_in.lock().unwrap();
+        if let Ok(access_token) = self.auth_rx.try_recv() {
+            *started = None;
+            return Ok(access_token);
+        }
+        match *started {
+            Some(at) if at.elapsed() < Duration::from_millis(config.auth_timeout_millis) => {
+                return Err(AuthError::Browser)
+            }
+            Some(_) => {
+                *started = None;
+                return Err(AuthError::Timeout);
+            }
+            None => (),
+        }
+
+        // Auth thread. The server outlives a failed attempt, so retries only reopen the browser.
+        if self.auth_server.is_completed() {
+            match (config.provider, account) {
+                (Provider::Outlook, Some(account)) => {
+                    auth::open_browser_authorization(account.outlook)
+                }
+                (Provider::Google, _) => google::open_browser_authorization(),
+                _ => (),
+            }
+        }
+        self.auth_server.call_once(|| {
+            let auth_tx = self.auth_tx.clone();
+            match (config.provider, account) {
+                (Provider::Outlook, Some(account)) => {
+                    self.auth.spawn(async move {
+                        auth::start_auth_server(auth_tx, account.outlook).await
+                    });
+                }
+                (Provider::Google, _) => {
+                    self.auth
+                        .spawn(async move { google::start_auth_server(auth_tx).await });
+                }
+                _ => (),
+            };
+        });
+        *started = Some(Instant::now());
+        Err(AuthError::Browser)
+    }
+
+    // Never blocks: the code is requested and polled for on the auth runtime, and each retry
+    // picks up how far that has got, requesting a new code once the old one expires.
+    fn device_code_sign_in(
+        &self,
+        outlook: &'static OutlookConfig,
+    ) -> Result<AccessToken, AuthError> {
+        let mut sign_in = self.device_code.lock().unwrap();
+        if let Ok(access_token) = self.auth_rx.try_recv() {
+            *sign_in = DeviceCodeSignIn::Idle;
+            return Ok(access_token);
+        }
+        if let Ok(requested) = self.device_code_rx.try_recv() {
+            let Some(device_code) = requested else {
+                *sign_in = DeviceCodeSignIn::Idle;
+                return Err(AuthError::DeviceCodeRequest);
+            };
+            *sign_in = DeviceCodeSignIn::Waiting(device_code);
+        }
+        match &*sign_in {
+            DeviceCodeSignIn::Requesting => return Err(AuthError::RequestingDeviceCode),
+            DeviceCodeSignIn::Waiting(device_code) if !device_code.is_expired() => {
+                return Err(AuthError::DeviceCode(device_code.clone()));
+            }
+            _ => (),
+        }
+
+        let device_code_tx = self.device_code_tx.clone();
+        let auth_tx = self.auth_tx.clone();
+        self.auth.spawn(async move {
+            let device_code = auth::request_device_code(outlook).await;
+            _ = device_code_tx.send(device_code.clone());
+            if let Some(device_code) = device_code {
+                auth::poll_device_code(device_code, auth_tx, outlook).await;
+            }
+        });
+        *sign_in = DeviceCodeSignIn::Requesting;
+        Err(AuthError::RequestingDeviceCode)
+    }
+}
+
+// SIGTERM, SIGHUP or SIGINT. Never returns if they can't be listened for.
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let (Ok(mut terminate), Ok(mut hangup), Ok(mut interrupt)) = (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::hangup()),
+        signal(SignalKind::interrupt()),
+    ) else {
+        return std::future::pending().await;
+    };
+    tokio::select! {
+        _ = terminate.recv() => (),
+        _ = hangup.recv() => (),
+        _ = interrupt.recv() => (),
+    }
+}
+
+// Ctrl-C, or closing the console window.
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    if tokio::signal::ctrl_c().await.is_err() {
+        std::future::pending().await
+    }
+}
+
+// Loads the token saved by a previous run and refreshes it. A token that no longer refreshes has
+// been revoked or has expired, so it is discarded and the browser flow takes over.
+async fn cached_token(provider: Provider, account: Option<Account<'_>>) -> Option<AccessToken> {
+    let name = account.and_then(|account| account.name);
+    let cached = auth::load_token(provider, name)?;
+    let refreshed = match (provider, account) {
+        (Provider::Outlook, Some(account)) => auth::refresh_token(&cached, account.outlook).await,
+        (Provider::Google, _) => google::refresh_token(&cached).await,
+        _ => None,
+    };
+    if refreshed.is_none() {
+        auth::clear_token(provider, name);
     }
+    refreshed
 }
@@ -0,0 +1,127 @@
+use std::process::Command;
+
+use reqwest::{Client, Method};
+
+use crate::{
+    backend::AuthError,
+    ics::parse_calendar,
+    outlook::CalendarEvent,
+    provider::{CalendarProvider, FetchError, Window},
+};
+
+pub struct CalDavProvider {
+    pub client: Client,
+    pub url: String,
+    pub username: String,
+    pub password: String,
+}
+
+impl CalendarProvider for CalDavProvider {
+    async fn fetch(&self, window: &Window) -> Result<Vec<CalendarEvent>, FetchError> {
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8" ?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop>
+    <C:calendar-data/>
+  </D:prop>
+  <C:filter>
+    <C:comp-filter name="VCALENDAR">
+      <C:comp-filter name="VEVENT">
+        <C:time-range start="{}" end="{}"/>
+      </C:comp-filter>
+    </C:comp-filter>
+  </C:filter>
+</C:calendar-query>"#,
+            window.start.format("%Y%m%dT%H%M%SZ"),
+            window.end.format("%Y%m%dT%H%M%SZ"),
+        );
+
+        let response = self
+            .client
+            .request(Method::from_bytes(b"REPORT").unwrap(), &self.url)
+            .basic_auth(&self.username, Some(&self.password))
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(body)
+            .send()
+            .await?;
+
+        let multistatus = FetchError::check(response)?.text().await?;
+        Ok(calendar_data(&multistatus)
+            .iter()
+            .flat_map(|calendar| parse_calendar(calendar, window))
+            .collect())
+    }
+}
+
+// Runs the configured command (e.g. `pass show caldav`) so the secret never lives in config.
+pub fn password(password_command: &str) -> Result<String, AuthError> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(password_command)
+        .output()
+        .map_err(|error| AuthError::PasswordCommand(error.to_string()))?;
+
+    match output.status.success() {
+        true => Ok(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        false => Err(AuthError::PasswordCommand(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )),
+    }
+}
+
+// Pulls the iCalendar payload out of each <calendar-data> element, whatever namespace prefix the
+// server chose.
+fn calendar_data(multistatus: &str) -> Vec<String> {
+    let mut calendars = Vec::new();
+    let mut rest = multistatus;
+
+    while let Some(tag) = rest.find("calendar-data") {
+        let (before, after) = rest.split_at(tag);
+        rest = &after["calendar-data".len()..];
+
+        // Only opening tags carry content; skip closing and self-closing ones.
+        let Some(open) = before.rfind('<') else {
+            continue;
+        };
+        let prefix = &before[open + 1..];
+        if prefix.starts_with('/') || prefix.contains(|c: char| c.is_whitespace() || c == '>') {
+            continue;
+        }
+        let Some(open_end) = rest.find('>') else {
+            break;
+        };
+        if rest[..open_end].ends_with('/') {
+            continue;
+        }
+        rest = &rest[open_end + 1..];
+
+        // CDATA payloads may themselves contain "</", so look for the CDATA terminator instead.
+        if let Some(cdata) = rest.trim_start().strip_prefix("<![CDATA[") {
+            let Some(close) = cdata.find("]]>") else {
+                break;
+            };
+            calendars.push(cdata[..close].to_string());
+            rest = &cdata[close..];
+            continue;
+        }
+
+        let Some(close) = rest.find("</") else {
+            break;
+        };
+        calendars.push(unescape_xml(&rest[..close]));
+        rest = &rest[close..];
+    }
+
+    calendars
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#13;", "\r")
+        .replace("&#xD;", "\r")
+        .replace("&amp;", "&")
+}
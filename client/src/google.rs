@@ -0,0 +1,373 @@
+use std::{
+    collections::HashMap,
+    sync::{mpsc::Sender, Arc, RwLock},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use graph_oauth::oauth::AccessToken;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+use tracing::{info, warn};
+use uuid::Uuid;
+use warp::{http::StatusCode, Filter};
+
+use crate::{
+    auth::save_token,
+    backend::http_client,
+    outlook::{
+        AttendeeResponse, CalendarEvent, EventAttendee, EventResponse, Importance, LinkSource,
+        RecurrenceSummary, ShowAs, TeamsMeeting,
+    },
+    provider::{CalendarProvider, FetchError, Provider, Window},
+    CONFIG,
+};
+
+static AUTHORIZE_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+static TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+static EVENTS_URL: &str = "https://www.googleapis.com/calendar/v3/calendars";
+static SCOPE: &str = "https://www.googleapis.com/auth/calendar.readonly";
+
+pub struct GoogleProvider {
+    pub token: Arc<RwLock<String>>,
+    pub client: Client,
+    pub calendar_id: String,
+}
+
+impl CalendarProvider for GoogleProvider {
+    async fn fetch(&self, window: &Window) -> Result<Vec<CalendarEvent>, FetchError> {
+        let url = format!("{}/{}/events", EVENTS_URL, self.calendar_id);
+        let token = self.token.read().unwrap().clone();
+
+        let mut events = Vec::new();
+        let mut page_token: Option<String> = None;
+
+        // Follow nextPageToken until Google reports no further pages.
+        loop {
+            let mut query = vec![
+                ("timeMin", window.start.to_rfc3339()),
+                ("timeMax", window.end.to_rfc3339()),
+                ("singleEvents", "true".to_string()),
+                ("orderBy", "startTime".to_string()),
+            ];
+            if let Some(page_token) = page_token.take() {
+                query.push(("pageToken", page_token));
+            }
+
+            let response = self
+                .client
+                .get(&url)
+                .query(&query)
+                .header("Authorization", format!("Bearer {}", token))
+                .send()
+                .await
+                .map_err(FetchError::from)
+                .and_then(FetchError::check);
+
+            let page = match response {
+                Ok(response) => response.json::<Events>().await.map_err(FetchError::from),
+                Err(error) => Err(error),
+            };
+
+            match page {
+                Ok(page) => {
+                    events.extend(page.items.iter().filter_map(to_calendar_event));
+                    match page.next_page_token {
+                        Some(next) => page_token = Some(next),
+                        None => break,
+                    }
+                }
+                // A partial listing would read as deletions of the events on the missing pages.
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+fn to_calendar_event(e: &Event) -> Option<CalendarEvent> {
+    let start_time = parse_google_time(&e.start)?;
+    let end_time = parse_google_time(&e.end)?;
+
+    let organizer = e
+        .organizer
+        .as_ref()
+        .and_then(|organizer| organizer.display_name.clone().or(organizer.email.clone()))
+        .unwrap_or_default();
+
+    let response = e
+        .attendees
+        .iter()
+        .find(|attendee| attendee.is_self)
+        .and_then(|attendee| match attendee.response_status.as_deref() {
+            Some("accepted") => Some(EventResponse::Accepted),
+            Some("tentative") => Some(EventResponse::TentativelyAccepted),
+            Some("declined") => Some(EventResponse::Declined),
+            Some("needsAction") => Some(EventResponse::NotResponded),
+            _ => None,
+        });
+
+    Some(CalendarEvent {
+        id: e.id.clone(),
+        body: e.description.clone().unwrap_or_default(),
+        raw_body: None,
+        i_cal_uid: e.ical_uid.clone(),
+        also_in: Vec::new(),
+        location: e.location.clone().unwrap_or_default(),
+        is_cancelled: e.status.as_deref() == Some("cancelled"),
+        is_all_day: e.start.date.is_some(),
+        start_time,
+        end_time,
+        subject: e.summary.clone().unwrap_or_default(),
+        organizer,
+        teams_meeting: e.hangout_link.clone().map(|url| TeamsMeeting {
+            url,
+            source: LinkSource::JoinUrl,
+        }),
+        web_link: e.html_link.clone(),
+        response,
+        attendees: e
+            .attendees
+            .iter()
+            .map(|attendee| EventAttendee {
+                name: attendee
+                    .display_name
+                    .clone()
+                    .or(attendee.email.clone())
+                    .unwrap_or_default(),
+                response: match attendee.response_status.as_deref() {
+                    Some("accepted") => AttendeeResponse::Accepted,
+                    Some("tentative") => AttendeeResponse::Tentative,
+                    Some("declined") => AttendeeResponse::Declined,
+                    _ => AttendeeResponse::NoResponse,
+                },
+            })
+            .collect(),
+        importance: Importance::Normal,
+        is_private: matches!(e.visibility.as_deref(), Some("private" | "confidential")),
+        categories: Vec::new(),
+        show_as: match (e.event_type.as_deref(), e.transparency.as_deref()) {
+            (Some("outOfOffice"), _) => ShowAs::OutOfOffice,
+            (Some("workingLocation"), _) => ShowAs::WorkingElsewhere,
+            (_, Some("transparent")) => ShowAs::Free,
+            _ => ShowAs::Busy,
+        },
+        calendar_name: None,
+        account: None,
+        // Expanded instances point at their series but don't carry its rule.
+        recurrence: e
+            .recurring_event_id
+            .as_ref()
+            .map(|_| RecurrenceSummary::default()),
+    })
+}
+
+fn parse_google_time(time: &EventDateTime) -> Option<DateTime<Utc>> {
+    match (&time.date_time, &time.date) {
+        (Some(date_time), _) => DateTime::parse_from_rfc3339(date_time)
+            .ok()
+            .map(|dt| dt.with_timezone(&Utc)),
+        // All-day events only carry a date.
+        (None, Some(date)) => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .and_then(|dt| Local.from_local_datetime(&dt).earliest())
+            .map(|dt| dt.with_timezone(&Utc)),
+        (None, None) => None,
+    }
+}
+
+// Echoed back on the redirect, so a login this session didn't start is rejected.
+static STATE: RwLock<String> = RwLock::new(String::new());
+
+pub fn open_browser_authorization() {
+    let google = &CONFIG.get().unwrap().google;
+    let state = Uuid::new_v4().to_string();
+    *STATE.write().unwrap() = state.clone();
+    let url = Url::parse_with_params(
+        AUTHORIZE_URL,
+        &[
+            ("client_id", google.client_id.as_str()),
+            ("redirect_uri", google.redirect_uri().as_str()),
+            ("response_type", "code"),
+            ("scope", SCOPE),
+            // Needed for Google to issue a refresh token.
+            ("access_type", "offline"),
+            ("prompt", "consent"),
+            ("state", state.as_str()),
+        ],
+    );
+    if let Ok(url) = url {
+        _ = webbrowser::open(url.as_str());
+    }
+}
+
+pub async fn handle_redirect(
+    query: HashMap<String, String>,
+    tx: Sender<AccessToken>,
+) -> Result<Box<dyn warp::Reply>, warp::Rejection> {
+    let expected_state = STATE.read().unwrap().clone();
+    let code = match (query.get("code"), query.get("state")) {
+        (Some(code), Some(state)) if !expected_state.is_empty() && *state == expected_state => {
+            code.clone()
+        }
+        _ => {
+            return Ok(Box::new(warp::reply::with_status(
+                "Sign-in rejected: the response did not match this session.",
+                StatusCode::BAD_REQUEST,
+            )))
+        }
+    };
+
+    let google = &CONFIG.get().unwrap().google;
+    let response = http_client()
+        .post(TOKEN_URL)
+        .form(&[
+            ("code", code.as_str()),
+            ("client_id", google.client_id.as_str()),
+            ("client_secret", google.client_secret.as_str()),
+            ("redirect_uri", google.redirect_uri().as_str()),
+            ("grant_type", "authorization_code"),
+        ])
+        .send()
+        .await;
+
+    if let Ok(response) = response {
+        if response.status().is_success() {
+            if let Ok(access_token) = response.json::<AccessToken>().await {
+                tx.send(access_token)
+                    .expect("ERROR: Could not send token between threads!");
+            }
+        }
+    }
+
+    // Generic login page response.
+    Ok(Box::new(
+        "Successfully Logged In! You can close your browser.",
+    ))
+}
+
+pub async fn start_auth_server(tx: Sender<AccessToken>) {
+    let routes = warp::get()
+        .and(warp::path("redirect"))
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(move |query| {
+            let tx = tx.clone();
+            handle_redirect(query, tx)
+        });
+
+    open_browser_authorization();
+
+    // Bind the same port the redirect_uri points at so the flow can complete.
+    let port = CONFIG.get().unwrap().google.redirect_port;
+    warp::serve(routes).run(([127, 0, 0, 1], port)).await;
+}
+
+// Google only issues the refresh token on the first exchange, so carry it over.
+pub async fn refresh_token(access_token: &AccessToken) -> Option<AccessToken> {
+    let google = &CONFIG.get().unwrap().google;
+    let refresh_token = access_token.refresh_token()?;
+
+    let response = http_client()
+        .post(TOKEN_URL)
+        .form(&[
+            ("refresh_token", refresh_token.as_str()),
+            ("client_id", google.client_id.as_str()),
+            ("client_secret", google.client_secret.as_str()),
+            ("grant_type", "refresh_token"),
+        ])
+        .send()
+        .await
+        .ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+
+    let mut refreshed = response.json::<AccessToken>().await.ok()?;
+    if refreshed.refresh_token().is_none() {
+        refreshed.set_refresh_token(refresh_token.as_str());
+    }
+    Some(refreshed)
+}
+
+pub async fn refresh_access_token(mut access_token: AccessToken, token: Arc<RwLock<String>>) {
+    let mut expires_in = access_token.expires_in();
+
+    loop {
+        // Wake up a minute before the current bearer expires.
+        sleep(Duration::from_secs((expires_in - 60).max(0) as u64)).await;
+
+        match refresh_token(&access_token).await {
+            Some(refreshed) => {
+                info!("refreshed the Google access token");
+                *token.write().unwrap() = refreshed.bearer_token().to_string();
+                save_token(Provider::Google, None, &refreshed);
+                expires_in = refreshed.expires_in();
+                access_token = refreshed;
+            }
+            // Retry shortly rather than letting the bearer lapse.
+            None => {
+                warn!("could not refresh the Google access token, retrying");
+                expires_in = 70;
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Events {
+    #[serde(default)]
+    pub items: Vec<Event>,
+    pub next_page_token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Event {
+    pub id: String,
+    #[serde(rename = "iCalUID")]
+    pub ical_uid: Option<String>,
+    pub status: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub hangout_link: Option<String>,
+    pub html_link: Option<String>,
+    pub recurring_event_id: Option<String>,
+    pub visibility: Option<String>,
+    pub transparency: Option<String>,
+    pub event_type: Option<String>,
+    pub organizer: Option<Person>,
+    #[serde(default)]
+    pub attendees: Vec<Attendee>,
+    pub start: EventDateTime,
+    pub end: EventDateTime,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventDateTime {
+    pub date: Option<String>,
+    pub date_time: Option<String>,
+    pub time_zone: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Person {
+    pub email: Option<String>,
+    pub display_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attendee {
+    pub email: Option<String>,
+    pub display_name: Option<String>,
+    pub response_status: Option<String>,
+    #[serde(rename = "self", default)]
+    pub is_self: bool,
+}
@@ -0,0 +1,608 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use reqwest::Client;
+
+use crate::{
+    outlook::{
+        find_join_link, to_tz, CalendarEvent, Importance, LinkSource, RecurrenceSummary, ShowAs,
+        TeamsMeeting,
+    },
+    provider::{CalendarProvider, FetchError, Window},
+};
+
+// Upper bound on generated instances per recurring event.
+const MAX_OCCURRENCES: usize = 1000;
+
+pub struct IcsProvider {
+    pub client: Client,
+    pub urls: Vec<String>,
+}
+
+impl CalendarProvider for IcsProvider {
+    async fn fetch(&self, window: &Window) -> Result<Vec<CalendarEvent>, FetchError> {
+        let mut events = Vec::new();
+        for url in &self.urls {
+            // A single unreachable feed shouldn't look like every event was removed.
+            let calendar = self.fetch_calendar(url).await?;
+            events.extend(parse_calendar(&calendar, window));
+        }
+        Ok(events)
+    }
+}
+
+impl IcsProvider {
+    async fn fetch_calendar(&self, url: &str) -> Result<String, FetchError> {
+        if url.starts_with("http://") || url.starts_with("https://") {
+            let response = self.client.get(url).send().await?;
+            Ok(FetchError::check(response)?.text().await?)
+        } else {
+            let path = url.strip_prefix("file://").unwrap_or(url);
+            tokio::fs::read_to_string(path)
+                .await
+                .map_err(|error| FetchError::Failed(format!("{path}: {error}")))
+        }
+    }
+}
+
+enum Zone {
+    Utc,
+    Tz(Tz),
+    Floating,
+}
+
+struct IcsTime {
+    naive: NaiveDateTime,
+    zone: Zone,
+    is_date: bool,
+}
+
+impl IcsTime {
+    fn parse(value: &str, params: &HashMap<String, String>) -> Option<Self> {
+        if params.get("VALUE").is_some_and(|v| v == "DATE") || value.len() == 8 {
+            let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+            return Some(Self {
+                naive: date.and_hms_opt(0, 0, 0)?,
+                zone: Zone::Floating,
+                is_date: true,
+            });
+        }
+
+        let (value, zone) = match value.strip_suffix('Z') {
+            Some(value) => (value, Zone::Utc),
+            None => match params.get("TZID").and_then(|tzid| to_tz(tzid)) {
+                Some(tz) => (value, Zone::Tz(tz)),
+                None => (value, Zone::Floating),
+            },
+        };
+        let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+        Some(Self {
+            naive,
+            zone,
+            is_date: false,
+        })
+    }
+
+    // Wall-clock times are resolved per instance so recurrences keep their local time across DST.
+    fn at(&self, naive: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match &self.zone {
+            Zone::Utc => Some(Utc.from_utc_datetime(&naive)),
+            Zone::Tz(tz) => tz
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|dt| dt.with_timezone(&Utc)),
+            Zone::Floating => chrono::Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|dt| dt.with_timezone(&Utc)),
+        }
+    }
+}
+
+struct Property {
+    params: HashMap<String, String>,
+    value: String,
+}
+
+// A VEVENT's properties, with the instants its EXDATEs remove.
+#[derive(Default)]
+struct VEvent {
+    properties: HashMap<String, Property>,
+    excluded: Vec<DateTime<Utc>>,
+}
+
+pub fn parse_calendar(calendar: &str, window: &Window) -> Vec<CalendarEvent> {
+    let mut vevents = Vec::new();
+    let mut current: Option<VEvent> = None;
+
+    for line in unfold(calendar) {
+        match line.as_str() {
+            "BEGIN:VEVENT" => current = Some(VEvent::default()),
+            "END:VEVENT" => vevents.extend(current.take()),
+            _ => {
+                let Some(vevent) = current.as_mut() else {
+                    continue;
+                };
+                let Some((name, property)) = parse_property(&line) else {
+                    continue;
+                };
+                // Unlike the rest, EXDATE may repeat and each line can list several dates.
+                if name == "EXDATE" {
+                    vevent.excluded.extend(
+                        property
+                            .value
+                            .split(',')
+                            .filter_map(|value| IcsTime::parse(value, &property.params))
+                            .filter_map(|time| time.at(time.naive)),
+                    );
+                } else {
+                    vevent.properties.entry(name).or_insert(property);
+                }
+            }
+        }
+    }
+
+    // A VEVENT with a RECURRENCE-ID stands in for the instance of its series starting then.
+    let mut overridden = HashMap::<String, Vec<DateTime<Utc>>>::new();
+    for VEvent { properties, .. } in &vevents {
+        if let (Some(uid), Some(recurrence_id)) = (
+            properties.get("UID"),
+            properties
+                .get("RECURRENCE-ID")
+                .and_then(|p| IcsTime::parse(&p.value, &p.params))
+                .and_then(|time| time.at(time.naive)),
+        ) {
+            overridden
+                .entry(unescape(&uid.value))
+                .or_default()
+                .push(recurrence_id);
+        }
+    }
+
+    vevents
+        .into_iter()
+        .flat_map(|mut vevent| {
+            if !vevent.properties.contains_key("RECURRENCE-ID") {
+                if let Some(instants) = vevent
+                    .properties
+                    .get("UID")
+                    .and_then(|uid| overridden.get(&unescape(&uid.value)))
+                {
+                    vevent.excluded.extend(instants);
+                }
+            }
+            to_calendar_events(&vevent.properties, &vevent.excluded, window)
+        })
+        .collect()
+}
+
+// Long lines are folded onto continuation lines starting with whitespace.
+fn unfold(calendar: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in calendar.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.trim_end().to_string()),
+        }
+    }
+    lines
+}
+
+fn parse_property(line: &str) -> Option<(String, Property)> {
+    let (key, value) = line.split_once(':')?;
+    let mut parts = key.split(';');
+    let name = parts.next()?.to_uppercase();
+    let params = parts
+        .filter_map(|param| param.split_once('='))
+        .map(|(k, v)| (k.to_uppercase(), v.trim_matches('"').to_string()))
+        .collect();
+    Some((
+        name,
+        Property {
+            params,
+            value: value.to_string(),
+        },
+    ))
+}
+
+// One pass, so an escaped backslash followed by "n" stays a backslash and an "n".
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(next) => unescaped.push(next),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+// Lines longer than 75 octets continue on the next line after a space, without splitting a
+// UTF-8 character.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+// Serializes events as an RFC 5545 calendar, one VEVENT per event.
+pub fn write_calendar<'a>(events: impl IntoIterator<Item = &'a CalendarEvent>) -> String {
+    let utc = |time: &DateTime<Utc>| time.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = |time: &DateTime<Utc>| DateTime::<Local>::from(*time).format("%Y%m%d").to_string();
+    let now = utc(&Utc::now());
+
+    let mut calendar =
+        fold("BEGIN:VCALENDAR") + &fold("VERSION:2.0") + &fold("PRODID:-//cal-tui//EN");
+    for event in events {
+        let mut lines = vec![
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}", escape(&event.id)),
+            format!("DTSTAMP:{now}"),
+        ];
+        match event.is_all_day {
+            true => lines.extend([
+                format!("DTSTART;VALUE=DATE:{}", date(&event.start_time)),
+                format!("DTEND;VALUE=DATE:{}", date(&event.end_time)),
+            ]),
+            false => lines.extend([
+                format!("DTSTART:{}", utc(&event.start_time)),
+                format!("DTEND:{}", utc(&event.end_time)),
+            ]),
+        }
+        lines.push(format!("SUMMARY:{}", escape(&event.subject)));
+        if !event.location.is_empty() {
+            lines.push(format!("LOCATION:{}", escape(&event.location)));
+        }
+        // Only the organizer's name is known, and the property needs an address, so it gets a
+        // placeholder one as Outlook's own exports do.
+        if !event.organizer.is_empty() {
+            lines.push(format!(
+                "ORGANIZER;CN=\"{}\":invalid:nomail",
+                event.organizer.replace('"', "")
+            ));
+        }
+        if !event.body.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape(&event.body)));
+        }
+        if let Some(url) = event.meeting_url() {
+            lines.push(format!("URL:{url}"));
+        }
+        lines.push("END:VEVENT".to_string());
+        calendar.extend(lines.iter().map(|line| fold(line)));
+    }
+    calendar + &fold("END:VCALENDAR")
+}
+
+fn to_calendar_events(
+    properties: &HashMap<String, Property>,
+    excluded: &[DateTime<Utc>],
+    window: &Window,
+) -> Vec<CalendarEvent> {
+    let text = |name: &str| {
+        properties
+            .get(name)
+            .map(|property| unescape(&property.value))
+            .unwrap_or_default()
+    };
+
+    let Some(start) = properties
+        .get("DTSTART")
+        .and_then(|p| IcsTime::parse(&p.value, &p.params))
+    else {
+        return Vec::new();
+    };
+    let duration = match properties
+        .get("DTEND")
+        .and_then(|p| IcsTime::parse(&p.value, &p.params))
+    {
+        Some(end) => end.naive - start.naive,
+        None if start.is_date => chrono::Duration::days(1),
+        None => chrono::Duration::zero(),
+    };
+
+    let organizer = properties
+        .get("ORGANIZER")
+        .map(|p| {
+            p.params.get("CN").cloned().unwrap_or_else(|| {
+                p.value
+                    .trim_start_matches("mailto:")
+                    .trim_start_matches("MAILTO:")
+                    .to_string()
+            })
+        })
+        .unwrap_or_default();
+
+    let uid = text("UID");
+    let subject = text("SUMMARY");
+    let location = text("LOCATION");
+    let body = text("DESCRIPTION");
+    let url = properties.get("URL").map(|p| p.value.clone());
+    let is_cancelled = text("STATUS").eq_ignore_ascii_case("CANCELLED");
+    let is_private = matches!(
+        text("CLASS").to_uppercase().as_str(),
+        "PRIVATE" | "CONFIDENTIAL"
+    );
+    // PRIORITY runs from 1 (highest) to 9 (lowest), with 0 meaning undefined.
+    let importance = match text("PRIORITY").parse::<u8>() {
+        Ok(1..=4) => Importance::High,
+        Ok(6..=9) => Importance::Low,
+        _ => Importance::Normal,
+    };
+    let show_as = match (
+        text("TRANSP").to_uppercase().as_str(),
+        text("STATUS").to_uppercase().as_str(),
+    ) {
+        ("TRANSPARENT", _) => ShowAs::Free,
+        (_, "TENTATIVE") => ShowAs::Tentative,
+        _ => ShowAs::Busy,
+    };
+    // Comma separated, with literal commas escaped.
+    let categories = properties
+        .get("CATEGORIES")
+        .map(|p| {
+            p.value
+                .replace("\\,", "\u{0}")
+                .split(',')
+                .map(|category| unescape(&category.replace('\u{0}', "\\,")))
+                .filter(|category| !category.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let rrule = properties.get("RRULE").map(|p| p.value.as_str());
+    let recurrence_id = properties
+        .get("RECURRENCE-ID")
+        .and_then(|p| IcsTime::parse(&p.value, &p.params))
+        .and_then(|time| time.at(time.naive));
+    let recurrence = rrule.map(|rrule| {
+        let rule = rrule
+            .split(';')
+            .filter_map(|part| part.split_once('='))
+            .collect::<HashMap<_, _>>();
+        RecurrenceSummary::new(
+            rule.get("FREQ").copied().unwrap_or_default(),
+            rule.get("INTERVAL")
+                .and_then(|i| i.parse().ok())
+                .unwrap_or(1),
+        )
+    });
+
+    // Feeds have no join link field; invites put it in the description.
+    let teams_meeting = find_join_link(&body).map(|url| TeamsMeeting {
+        url,
+        source: LinkSource::Body,
+    });
+
+    occurrences(&start, duration, rrule, window)
+        .into_iter()
+        .filter_map(|naive| {
+            let start_time = start.at(naive)?;
+            let end_time = start.at(naive + duration)?;
+            if excluded.contains(&start_time) {
+                return None;
+            }
+            (end_time > window.start && start_time < window.end).then(|| CalendarEvent {
+                // Recurrence instances share a UID, so qualify it with the start. A moved
+                // instance keeps the id of the one it replaces.
+                id: match (rrule, recurrence_id) {
+                    (Some(_), _) => format!("{}-{}", uid, start_time.timestamp()),
+                    (None, Some(recurrence_id)) => {
+                        format!("{}-{}", uid, recurrence_id.timestamp())
+                    }
+                    (None, None) => uid.clone(),
+                },
+                body: body.clone(),
+                raw_body: None,
+                i_cal_uid: Some(uid.clone()),
+                also_in: Vec::new(),
+                location: location.clone(),
+                is_cancelled,
+                is_all_day: start.is_date,
+                start_time,
+                end_time,
+                organizer: organizer.clone(),
+                subject: subject.clone(),
+                teams_meeting: teams_meeting.clone(),
+                web_link: url.clone(),
+                response: None,
+                recurrence: recurrence.clone(),
+                // Only the first of a repeated property is kept, so attendees aren't listed.
+                attendees: Vec::new(),
+                importance,
+                is_private,
+                categories: categories.clone(),
+                show_as,
+                calendar_name: None,
+                account: None,
+            })
+        })
+        .collect()
+}
+
+// Expands basic DAILY/WEEKLY RRULEs into concrete start times up to the end of the window.
+fn occurrences(
+    start: &IcsTime,
+    duration: chrono::Duration,
+    rrule: Option<&str>,
+    window: &Window,
+) -> Vec<NaiveDateTime> {
+    let Some(rrule) = rrule else {
+        return vec![start.naive];
+    };
+
+    let rule = rrule
+        .split(';')
+        .filter_map(|part| part.split_once('='))
+        .collect::<HashMap<_, _>>();
+    let interval = rule
+        .get("INTERVAL")
+        .and_then(|i| i.parse::<u64>().ok())
+        .unwrap_or(1)
+        .max(1);
+    let count = rule.get("COUNT").and_then(|c| c.parse::<usize>().ok());
+    let until = rule.get("UNTIL").and_then(|until| {
+        IcsTime::parse(until, &HashMap::new()).and_then(|until| until.at(until.naive))
+    });
+    let by_day = rule
+        .get("BYDAY")
+        .map(|days| days.split(',').filter_map(to_weekday).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let step = match rule.get("FREQ") {
+        Some(&"DAILY") => 1,
+        Some(&"WEEKLY") => 7,
+        // Unsupported frequencies only show their first instance.
+        _ => return vec![start.naive],
+    };
+
+    let week_start =
+        start.naive.date() - Days::new(start.naive.weekday().num_days_from_monday() as u64);
+    let mut instances = Vec::new();
+    let mut generated = 0;
+    let mut day = 0;
+
+    while instances.len() < MAX_OCCURRENCES {
+        let Some(candidate) = start.naive.checked_add_days(Days::new(day)) else {
+            break;
+        };
+        day += 1;
+
+        let matches = if step == 7 && !by_day.is_empty() {
+            let week = (candidate.date() - week_start).num_days() as u64 / 7;
+            week.is_multiple_of(interval) && by_day.contains(&candidate.weekday())
+        } else {
+            (day - 1) % (step * interval) == 0
+        };
+        if !matches {
+            continue;
+        }
+
+        let Some(candidate_utc) = start.at(candidate) else {
+            continue;
+        };
+        if candidate_utc >= window.end
+            || until.is_some_and(|until| candidate_utc > until)
+            || count.is_some_and(|count| generated >= count)
+        {
+            break;
+        }
+
+        // Instances before the window still count towards COUNT.
+        generated += 1;
+        if candidate_utc + duration > window.start {
+            instances.push(candidate);
+        }
+    }
+
+    instances
+}
+
+fn to_weekday(day: &str) -> Option<Weekday> {
+    // Ordinal prefixes like "1MO" only make sense for monthly rules.
+    match day.trim_start_matches(|c: char| c.is_ascii_digit() || c == '-' || c == '+') {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window() -> Window {
+        Window {
+            start: Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 3, 8, 0, 0, 0).unwrap(),
+        }
+    }
+
+    fn starts(events: &[CalendarEvent]) -> Vec<String> {
+        let mut starts = events
+            .iter()
+            .map(|e| e.start_time.format("%d %H:%M").to_string())
+            .collect::<Vec<_>>();
+        starts.sort();
+        starts
+    }
+
+    #[test]
+    fn unescape_is_a_single_pass() {
+        assert_eq!(unescape(r"a\,b\;c\nd"), "a,b;c\nd");
+        assert_eq!(unescape(r"C:\\new"), r"C:\new");
+        assert_eq!(unescape(r"trailing\"), r"trailing\");
+    }
+
+    #[test]
+    fn exdates_are_skipped() {
+        let calendar = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:standup\r\n\
+            DTSTART:20240301T090000Z\r\n\
+            DTEND:20240301T091500Z\r\n\
+            RRULE:FREQ=DAILY;COUNT=5\r\n\
+            EXDATE:20240302T090000Z,20240303T090000Z\r\n\
+            EXDATE:20240305T090000Z\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        let events = parse_calendar(calendar, &window());
+        assert_eq!(starts(&events), ["01 09:00", "04 09:00"]);
+    }
+
+    #[test]
+    fn recurrence_id_replaces_its_instance() {
+        let calendar = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:standup\r\n\
+            SUMMARY:Standup\r\n\
+            DTSTART:20240301T090000Z\r\n\
+            DTEND:20240301T091500Z\r\n\
+            RRULE:FREQ=DAILY;COUNT=3\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:standup\r\n\
+            SUMMARY:Standup (moved)\r\n\
+            RECURRENCE-ID:20240302T090000Z\r\n\
+            DTSTART:20240302T140000Z\r\n\
+            DTEND:20240302T141500Z\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        let events = parse_calendar(calendar, &window());
+        assert_eq!(starts(&events), ["01 09:00", "02 14:00", "03 09:00"]);
+
+        let moved = events
+            .iter()
+            .find(|e| e.subject == "Standup (moved)")
+            .unwrap();
+        let replaced = Utc.with_ymd_and_hms(2024, 3, 2, 9, 0, 0).unwrap();
+        assert_eq!(moved.id, format!("standup-{}", replaced.timestamp()));
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+use crossterm::event::KeyCode;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    FocusTable,
+    FocusSelected,
+    Next,
+    Prev,
+    Accept,
+    Decline,
+    OpenLink,
+    CopyLink,
+    Retry,
+    Filter,
+    ClearFilter,
+    JumpToNow,
+    Dismiss,
+    Snooze,
+    WeekView,
+    PrevWeek,
+    NextWeek,
+    Help,
+    CycleCalendar,
+    Export,
+    Refresh,
+    CycleAccount,
+    WorkingHours,
+    FreeBusy,
+    RawBody,
+    CycleTheme,
+}
+
+impl Action {
+    pub const ALL: [Action; 27] = [
+        Action::Quit,
+        Action::FocusTable,
+        Action::FocusSelected,
+        Action::Next,
+        Action::Prev,
+        Action::Accept,
+        Action::Decline,
+        Action::OpenLink,
+        Action::CopyLink,
+        Action::Retry,
+        Action::Filter,
+        Action::ClearFilter,
+        Action::JumpToNow,
+        Action::Dismiss,
+        Action::Snooze,
+        Action::WeekView,
+        Action::PrevWeek,
+        Action::NextWeek,
+        Action::Help,
+        Action::CycleCalendar,
+        Action::Export,
+        Action::Refresh,
+        Action::CycleAccount,
+        Action::WorkingHours,
+        Action::FreeBusy,
+        Action::RawBody,
+        Action::CycleTheme,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::FocusTable => "focus_table",
+            Action::FocusSelected => "focus_selected",
+            Action::Next => "next",
+            Action::Prev => "prev",
+            Action::Accept => "accept",
+            Action::Decline => "decline",
+            Action::OpenLink => "open_link",
+            Action::CopyLink => "copy_link",
+            Action::Retry => "retry",
+            Action::Filter => "filter",
+            Action::ClearFilter => "clear_filter",
+            Action::JumpToNow => "jump_to_now",
+            Action::Dismiss => "dismiss",
+            Action::Snooze => "snooze",
+            Action::WeekView => "week_view",
+            Action::PrevWeek => "prev_week",
+            Action::NextWeek => "next_week",
+            Action::Help => "help",
+            Action::CycleCalendar => "cycle_calendar",
+            Action::Export => "export",
+            Action::Refresh => "refresh",
+            Action::CycleAccount => "cycle_account",
+            Action::WorkingHours => "working_hours",
+            Action::FreeBusy => "free_busy",
+            Action::RawBody => "raw_body",
+            Action::CycleTheme => "cycle_theme",
+        }
+    }
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q"],
+            Action::FocusTable => &["h"],
+            Action::FocusSelected => &["l"],
+            Action::Next => &["j", "down"],
+            Action::Prev => &["k", "up"],
+            Action::Accept => &["a"],
+            Action::Decline => &["r"],
+            Action::OpenLink => &["o"],
+            Action::CopyLink => &["y"],
+            Action::Retry => &["r"],
+            Action::Filter => &["/"],
+            Action::ClearFilter => &["esc"],
+            Action::JumpToNow => &["t"],
+            Action::Dismiss => &["esc", "d"],
+            Action::Snooze => &["s"],
+            Action::WeekView => &["w"],
+            Action::PrevWeek => &["h"],
+            Action::NextWeek => &["l"],
+            Action::Help => &["?"],
+            Action::CycleCalendar => &["c"],
+            Action::Export => &["e"],
+            Action::Refresh => &["r"],
+            Action::CycleAccount => &["p"],
+            Action::WorkingHours => &["H"],
+            Action::FreeBusy => &["f"],
+            Action::RawBody => &["b"],
+            Action::CycleTheme => &["T"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "HashMap<String, Vec<String>>")]
+pub struct Keybindings {
+    bindings: HashMap<Action, Vec<KeyCode>>,
+}
+
+impl Keybindings {
+    // Actions bound to a key. One key may serve several actions in different focuses.
+    pub fn actions(&self, key: KeyCode) -> Vec<Action> {
+        Action::ALL
+            .into_iter()
+            .filter(|action| {
+                self.bindings
+                    .get(action)
+                    .is_some_and(|keys| keys.contains(&key))
+            })
+            .collect()
+    }
+
+    pub fn keys(&self, action: Action) -> String {
+        self.bindings
+            .get(&action)
+            .map(|keys| keys.iter().map(key_name).collect::<Vec<_>>().join("/"))
+            .unwrap_or_default()
+    }
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .into_iter()
+            .map(|action| {
+                let keys = action
+                    .default_keys()
+                    .iter()
+                    .filter_map(|key| parse_key(key))
+                    .collect();
+                (action, keys)
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl TryFrom<HashMap<String, Vec<String>>> for Keybindings {
+    type Error = String;
+
+    // Configured actions replace their defaults; the rest keep them.
+    fn try_from(config: HashMap<String, Vec<String>>) -> Result<Self, Self::Error> {
+        let mut keybindings = Keybindings::default();
+        for (name, keys) in config {
+            let action = Action::ALL
+                .into_iter()
+                .find(|action| action.name() == name)
+                .ok_or_else(|| format!("Unknown keybinding action \"{name}\""))?;
+            let keys = keys
+                .iter()
+                .map(|key| {
+                    parse_key(key).ok_or_else(|| format!("Unknown key \"{key}\" for {name}"))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            keybindings.bindings.insert(action, keys);
+        }
+        Ok(keybindings)
+    }
+}
+
+fn parse_key(key: &str) -> Option<KeyCode> {
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => return Some(KeyCode::Char(c)),
+        (None, _) => return None,
+        _ => (),
+    }
+
+    match key.to_lowercase().as_str() {
+        "up" => Some(KeyCode::Up),
+        "down" => Some(KeyCode::Down),
+        "left" => Some(KeyCode::Left),
+        "right" => Some(KeyCode::Right),
+        "enter" => Some(KeyCode::Enter),
+        "esc" => Some(KeyCode::Esc),
+        "tab" => Some(KeyCode::Tab),
+        "backspace" => Some(KeyCode::Backspace),
+        "space" => Some(KeyCode::Char(' ')),
+        "home" => Some(KeyCode::Home),
+        "end" => Some(KeyCode::End),
+        "pageup" => Some(KeyCode::PageUp),
+        "pagedown" => Some(KeyCode::PageDown),
+        _ => None,
+    }
+}
+
+fn key_name(key: &KeyCode) -> String {
+    match key {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::Backspace => "backspace".to_string(),
+        KeyCode::Home => "home".to_string(),
+        KeyCode::End => "end".to_string(),
+        KeyCode::PageUp => "pageup".to_string(),
+        KeyCode::PageDown => "pagedown".to_string(),
+        key => format!("{key:?}"),
+    }
+}
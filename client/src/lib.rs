@@ -0,0 +1,26 @@
+// The calendar model, providers and TUI behind the cal-tui binary, for embedding it elsewhere.
+// CONFIG must be set before creating a Backend or App, e.g. from Config::from_path().
+
+use std::{path::PathBuf, sync::OnceLock};
+
+pub mod agenda;
+pub mod app;
+mod auth;
+pub mod backend;
+mod caldav;
+mod google;
+mod ics;
+pub mod keybindings;
+mod mock;
+pub mod outlook;
+pub mod provider;
+mod state;
+pub mod ui;
+
+pub use app::{App, Config};
+pub use backend::Backend;
+pub use outlook::CalendarEvent;
+pub use provider::{CalendarProvider, FetchError, Window};
+
+pub static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+pub static CONFIG: OnceLock<Config> = OnceLock::new();
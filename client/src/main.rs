@@ -1,49 +1,142 @@
+use clap::Parser;
 use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
+use tracing_appender::{non_blocking::WorkerGuard, rolling};
+use tracing_subscriber::filter::LevelFilter;
 
 use std::{
     io::{self, stdout},
-    sync::OnceLock,
+    panic,
+    path::PathBuf,
+    thread,
 };
 
-mod app;
-mod auth;
-mod outlook;
-use app::App;
-mod backend;
-mod ui;
-use backend::*;
+use cal_tui::{agenda, app, app::ConfigError, App, Backend, Config, CONFIG, CONFIG_PATH};
 
-use crate::app::Config;
-
-// static CONFIG_PATH: &str = "$HOME/.config/cal-tui/config.toml";
-static CONFIG_PATH: OnceLock<&str> = OnceLock::new();
-static CONFIG: OnceLock<Config> = OnceLock::new();
+#[derive(Parser)]
+#[command(version, about = "Upcoming meetings in your terminal")]
+struct Cli {
+    #[arg(long, help = "Print upcoming events and exit")]
+    agenda: bool,
+    #[arg(long, requires = "agenda", help = "Print the agenda as JSON")]
+    json: bool,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Save upcoming events to an .ics file and exit"
+    )]
+    export_ics: Option<PathBuf>,
+    #[arg(
+        long,
+        conflicts_with_all = ["agenda", "export_ics"],
+        help = "Keep running, printing each event change as a line of JSON"
+    )]
+    watch_json: bool,
+    #[arg(long, help = "Show made-up events instead of signing in")]
+    demo: bool,
+}
 
 fn main() -> io::Result<()> {
+    let cli = Cli::parse();
+
+    // $XDG_CONFIG_HOME or ~/.config on Linux, the roaming AppData folder on Windows.
     CONFIG_PATH.get_or_init(|| {
-        if cfg!(unix) {
-            "$HOME/.config/cal-tui/config.toml"
-        } else {
-            "%APPDATA%\\cal-tui\\config.toml"
+        dirs::config_dir()
+            .expect("ERROR: Could not resolve a config directory!")
+            .join("cal-tui")
+            .join("config.toml")
+    });
+    // Report config mistakes before raw mode, so the terminal is left untouched.
+    let config = match cli.demo {
+        true => Config::demo(),
+        false => Config::from_path(),
+    }
+    .unwrap_or_else(|error| match error {
+        ConfigError::Created(_) => {
+            println!("{error}");
+            std::process::exit(0);
+        }
+        error => {
+            eprintln!("ERROR: {error}");
+            std::process::exit(1);
         }
     });
-    CONFIG.get_or_init(Config::from_path);
+    CONFIG.get_or_init(|| config);
+    // Flushes buffered log lines when dropped at exit.
+    let _log_guard = init_logging(CONFIG.get().unwrap());
+
+    if cli.agenda {
+        return agenda::run(cli.json);
+    }
+    if let Some(path) = cli.export_ics {
+        return agenda::export(&path);
+    }
+    if cli.watch_json {
+        return agenda::watch();
+    }
 
-    enable_raw_mode()?;
-    stdout().execute(EnterAlternateScreen)?;
+    // A panic message printed inside the alternate screen is lost, so restore first. Panics on
+    // runtime threads are caught by tokio and the UI keeps running, so leave those alone.
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if thread::current().name() == Some("main") {
+            restore_terminal();
+        }
+        default_hook(info);
+    }));
 
+    let _guard = TerminalGuard::new()?;
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
     let backend = Backend::new();
     let app = App::new(backend);
 
-    app.run(&mut terminal).unwrap();
+    app.run(&mut terminal)
+}
 
-    disable_raw_mode()?;
-    stdout().execute(LeaveAlternateScreen)?;
+// Logs go to a file, since anything printed would land on top of the TUI.
+fn init_logging(config: &Config) -> Option<WorkerGuard> {
+    let level = config.log_level.parse::<LevelFilter>().ok()?;
+    if level == LevelFilter::OFF {
+        return None;
+    }
+    let path = config
+        .log_file
+        .clone()
+        .unwrap_or_else(|| app::config_dir().join("cal-tui.log"));
+    let directory = path.parent()?;
+    let file_name = path.file_name()?;
+
+    let (writer, guard) = tracing_appender::non_blocking(rolling::daily(directory, file_name));
+    tracing_subscriber::fmt()
+        .with_writer(writer)
+        .with_ansi(false)
+        .with_max_level(level)
+        .init();
+    Some(guard)
+}
+
+// Puts the terminal into raw mode on the alternate screen, and restores it when dropped, whether
+// the event loop returned normally, bailed out with `?`, or unwound from a panic.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> io::Result<Self> {
+        enable_raw_mode()?;
+        stdout().execute(EnterAlternateScreen)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
 
-    Ok(())
+fn restore_terminal() {
+    _ = disable_raw_mode();
+    _ = stdout().execute(LeaveAlternateScreen);
 }
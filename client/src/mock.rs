@@ -0,0 +1,119 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+
+use crate::{
+    outlook::{
+        AttendeeResponse, CalendarEvent, EventAttendee, EventResponse, Importance, LinkSource,
+        RecurrenceSummary, ShowAs, TeamsMeeting,
+    },
+    provider::{CalendarProvider, FetchError, Window},
+};
+
+// Canned events for demos and UI work, so nothing needs an account.
+pub struct MockProvider {
+    // The top of the hour the provider was created in. Events are placed relative to it, so they
+    // stay put across refreshes.
+    pub anchor: DateTime<Utc>,
+}
+
+impl MockProvider {
+    pub fn from_now() -> Self {
+        let now = Utc::now();
+        Self {
+            anchor: now.duration_trunc(Duration::hours(1)).unwrap_or(now),
+        }
+    }
+}
+
+impl CalendarProvider for MockProvider {
+    async fn fetch(&self, window: &Window) -> Result<Vec<CalendarEvent>, FetchError> {
+        Ok(events(self.anchor)
+            .into_iter()
+            .filter(|event| event.end_time > window.start && event.start_time < window.end)
+            .collect())
+    }
+}
+
+// Minutes after the anchor, length in minutes, and subject.
+const SCHEDULE: [(i64, i64, &str); 10] = [
+    (60, 15, "Daily stand-up"),
+    (90, 60, "Design review"),
+    (180, 30, "1:1 with Sam"),
+    (240, 45, "Customer call"),
+    (300, 60, "Focus time"),
+    (24 * 60 + 60, 15, "Daily stand-up"),
+    (24 * 60 + 120, 90, "Quarterly planning"),
+    (24 * 60 + 300, 30, "Interview debrief"),
+    (2 * 24 * 60, 24 * 60, "Company offsite"),
+    (3 * 24 * 60 + 120, 60, "Lunch & learn"),
+];
+
+pub(crate) fn events(anchor: DateTime<Utc>) -> Vec<CalendarEvent> {
+    SCHEDULE
+        .iter()
+        .enumerate()
+        .map(|(i, (offset, length, subject))| {
+            let start_time = anchor + Duration::minutes(*offset);
+            CalendarEvent {
+                id: format!("mock-{i}"),
+                body: format!("Agenda for {subject}.\n\nNotes go here."),
+                raw_body: None,
+                i_cal_uid: None,
+                also_in: Vec::new(),
+                location: match i % 3 {
+                    0 => "Microsoft Teams Meeting".to_string(),
+                    1 => "Room 4.02".to_string(),
+                    _ => String::new(),
+                },
+                is_cancelled: false,
+                is_all_day: *length == 24 * 60,
+                start_time,
+                end_time: start_time + Duration::minutes(*length),
+                organizer: ["Alex Kim", "Jordan Lee", "Sam Patel"][i % 3].to_string(),
+                subject: subject.to_string(),
+                teams_meeting: (i % 3 == 0).then(|| TeamsMeeting {
+                    url: format!("https://teams.microsoft.com/l/meetup-join/mock-{i}"),
+                    source: LinkSource::JoinUrl,
+                }),
+                web_link: None,
+                response: Some(match i % 4 {
+                    0 | 1 => EventResponse::Accepted,
+                    2 => EventResponse::NotResponded,
+                    _ => EventResponse::Declined,
+                }),
+                recurrence: (*subject == "Daily stand-up")
+                    .then(|| RecurrenceSummary::new("daily", 1)),
+                attendees: vec![
+                    EventAttendee {
+                        name: "Alex Kim".to_string(),
+                        response: AttendeeResponse::Accepted,
+                    },
+                    EventAttendee {
+                        name: "Jordan Lee".to_string(),
+                        response: AttendeeResponse::Tentative,
+                    },
+                    EventAttendee {
+                        name: "Sam Patel".to_string(),
+                        response: AttendeeResponse::NoResponse,
+                    },
+                ],
+                importance: match i {
+                    3 => Importance::High,
+                    _ => Importance::Normal,
+                },
+                is_private: i == 2,
+                categories: match i % 2 {
+                    0 => vec!["Work".to_string()],
+                    _ => Vec::new(),
+                },
+                show_as: match i {
+                    4 => ShowAs::Free,
+                    6 => ShowAs::Tentative,
+                    8 => ShowAs::OutOfOffice,
+                    _ => ShowAs::Busy,
+                },
+                calendar_name: None,
+                account: None,
+            }
+        })
+        .collect()
+}
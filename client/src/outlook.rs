@@ -1,180 +1,846 @@
-use std::{fmt, sync::mpsc::Sender, time::Duration};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{mpsc::Sender, Arc, Mutex, RwLock},
+};
 
-use chrono::{DateTime, Days, Timelike, Utc};
-use reqwest::Client;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
-use tokio::time::sleep;
+use tracing::warn;
 
-use crate::CONFIG;
+use crate::{
+    app::OutlookConfig,
+    auth,
+    backend::BackendError,
+    provider::{CalendarProvider, FetchError, Provider, Window},
+};
 
-pub async fn refresh(token: String, client: Client, event_tx: Sender<CalendarEvent>) {
-    loop {
-        let start = Utc::now();
-        let end = start
-            .checked_add_days(Days::new(CONFIG.get().unwrap().limit_days))
-            .unwrap();
-
-        let start_arg = format!(
-            "{}T{}",
-            start.date_naive(),
-            start.time().to_string().rsplit_once(':').unwrap().0
-        );
-        let end_arg = format!(
-            "{}T{}",
-            end.date_naive(),
-            start.time().to_string().rsplit_once(':').unwrap().0,
-        );
+pub struct OutlookProvider {
+    pub token: Arc<RwLock<String>>,
+    // For renewing the token when Graph rejects it.
+    pub outlook: &'static OutlookConfig,
+    pub client: Client,
+    pub base_url: String,
+    pub error_tx: Sender<BackendError>,
+    pub delta: Mutex<Option<Delta>>,
+    // None for the account's default calendar. Shared with the UI, which can switch it.
+    pub calendar_id: Arc<RwLock<Option<String>>>,
+    // Set when this calendar is merged with others, to label its events.
+    pub calendar_name: Option<String>,
+    // Name of the [[accounts]] entry signed in to, if any.
+    pub account: Option<String>,
+}
 
-        let url = format!(
-            "{}?startDateTime={}&endDateTime={}",
-            CONFIG.get().unwrap().outlook.base_url,
-            start_arg,
-            end_arg
-        );
+// Where the last delta sync left off, and every event it has seen so far.
+pub struct Delta {
+    link: String,
+    synced_on: NaiveDate,
+    calendar_id: Option<String>,
+    events: HashMap<String, CalendarEvent>,
+}
 
-        if Utc::now().second() % CONFIG.get().unwrap().refresh_period_seconds == 0 {
-            // refresh
-            let response = client
-                .get(url)
-                .header("Authorization", format!("Bearer {}", token))
-                .send()
-                .await;
-
-            if let Ok(response) = response {
-                let res = response.json::<Root>().await;
-                if let Ok(res) = res {
-                    let calendar_events = res
-                        .value
-                        .iter()
-                        .map(|v| {
-                            let start_time_string =
-                                format!("{}+0000", v.start.date_time.clone().unwrap());
-                            let start_time = DateTime::parse_from_str(
-                                &start_time_string,
-                                "%Y-%m-%dT%H:%M:%S%.f%z",
-                            )
-                            .ok()
-                            .map(|dt| dt.with_timezone(&Utc::now().timezone()))
-                            .unwrap();
-                            let end_time_string =
-                                format!("{}+0000", v.end.date_time.clone().unwrap());
-                            let end_time = DateTime::parse_from_str(
-                                &end_time_string,
-                                "%Y-%m-%dT%H:%M:%S%.f%z",
-                            )
-                            .ok()
-                            .map(|dt| dt.with_timezone(&Utc::now().timezone()))
-                            .unwrap();
-
-                            let id = v.id.clone().expect("ERROR: Event has no ID");
-                            let is_cancelled = v.is_cancelled;
-                            let organizer = v
-                                .organizer
-                                .email_address
-                                .name
-                                .clone()
-                                .expect("ERROR: Event has no organizer");
-                            let subject = v.subject.clone().expect("ERROR: Event has no subject");
-
-                            let teams_meeting: Option<TeamsMeeting> = match v.is_online_meeting {
-                                true => Some(TeamsMeeting {
-                                    url: v.online_meeting_url.clone().unwrap_or("".to_string()),
-                                }),
-                                false => None,
-                            };
-
-                            let response: Option<EventResponse> =
-                                match v.response_status.response.as_ref() {
-                                    Some(status) => match status.as_ref() {
-                                        "accepted" => Some(EventResponse::Accepted),
-                                        "notResponded" => Some(EventResponse::NotResponded),
-                                        _ => None,
-                                    },
-                                    None => None,
-                                };
-
-                            let location = v
-                                .location
-                                .clone()
-                                .unwrap_or_default()
-                                .display_name
-                                .unwrap_or_default();
-
-                            let body = v.body_preview.clone().unwrap_or_default();
-
-                            CalendarEvent {
-                                id,
-                                body,
-                                location,
-                                is_cancelled,
-                                start_time,
-                                end_time,
-                                subject,
-                                organizer,
-                                teams_meeting,
-                                response,
-                            }
-                        })
-                        .filter(|e| !e.is_cancelled && e.start_time > Utc::now());
-
-                    for event in calendar_events {
-                        event_tx
-                            .send(event)
-                            .expect("ERROR: Could not send message to main thread");
-                    }
+impl CalendarProvider for OutlookProvider {
+    // Only the first poll downloads the whole window; later ones ask Graph for changes since the
+    // previous deltaLink and apply them to the events already seen.
+    async fn fetch(&self, window: &Window) -> Result<Vec<CalendarEvent>, FetchError> {
+        let token = self.token.read().unwrap().clone();
+        match self.sync(window, &token).await {
+            // The scheduled refresh can miss, e.g. while the machine was asleep, so renew now
+            // and try once more.
+            Err(FetchError::Unauthorized) => {
+                let token = self.renew_token().await.ok_or(FetchError::Unauthorized)?;
+                self.sync(window, &token).await
+            }
+            result => result,
+        }
+    }
+}
+
+impl OutlookProvider {
+    async fn sync(&self, window: &Window, token: &str) -> Result<Vec<CalendarEvent>, FetchError> {
+        let calendar_id = self.calendar_id.read().unwrap().clone();
+
+        // A deltaLink keeps the window it started with, so start over each day to let the
+        // horizon slide forward. It is also tied to one calendar.
+        let previous = self.delta.lock().unwrap().take().filter(|delta| {
+            delta.synced_on == Local::now().date_naive() && delta.calendar_id == calendar_id
+        });
+
+        let page = match &previous {
+            Some(delta) => fetch_delta(&self.client, delta.link.clone(), token).await?,
+            None => None,
+        };
+        let (mut events, (changes, link)) = match (previous, page) {
+            (Some(delta), Some(page)) => (delta.events, page),
+            // First sync, a new day, another calendar, or Graph expired the deltaLink (410 Gone).
+            _ => {
+                let (start_arg, end_arg) = query_window(window);
+                let mut url = format!(
+                    "{}/delta?startDateTime={}&endDateTime={}",
+                    calendar_view_url(&self.base_url, calendar_id.as_deref()),
+                    start_arg,
+                    end_arg
+                );
+                // Later deltaLinks carry the $select on.
+                if let Some(select) = self.outlook.select_query() {
+                    url.push_str(&format!("&$select={select}"));
+                }
+                let page = fetch_delta(&self.client, url, token)
+                    .await?
+                    .ok_or_else(|| FetchError::Failed("delta sync expired".to_string()))?;
+                (HashMap::new(), page)
+            }
+        };
+
+        let mut cancelled = Vec::new();
+        for change in changes {
+            match change {
+                DeltaValue::Removed { id, .. } => {
+                    events.remove(&id);
                 }
+                // One malformed event is reported and skipped rather than failing the whole
+                // refresh.
+                DeltaValue::Changed(value) => match to_calendar_event(&value) {
+                    Ok(event) if event.is_cancelled => {
+                        events.remove(&event.id);
+                        cancelled.push(event);
+                    }
+                    Ok(mut event) => {
+                        event.calendar_name = self.calendar_name.clone();
+                        event.account = self.account.clone();
+                        events.insert(event.id.clone(), event);
+                    }
+                    Err(error) => {
+                        warn!("{error}");
+                        _ = self.error_tx.send(error);
+                    }
+                },
+            }
+        }
+
+        // Removed events simply drop out of the result, which refresh turns into removals.
+        let result = events.values().cloned().chain(cancelled).collect();
+        *self.delta.lock().unwrap() = Some(Delta {
+            link,
+            synced_on: Local::now().date_naive(),
+            calendar_id,
+            events,
+        });
+        Ok(result)
+    }
+
+    // None when there is no refresh token or Microsoft rejects it too, leaving a new sign-in.
+    async fn renew_token(&self) -> Option<String> {
+        let account = self.account.as_deref();
+        let access_token = auth::load_token(Provider::Outlook, account)?;
+        let refreshed = auth::refresh_token(&access_token, self.outlook).await?;
+        auth::save_token(Provider::Outlook, account, &refreshed);
+
+        let bearer = refreshed.bearer_token().to_string();
+        *self.token.write().unwrap() = bearer.clone();
+        warn!(account, "renewed a rejected Outlook access token");
+        Some(bearer)
+    }
+}
+
+pub async fn respond(
+    client: &Client,
+    events_url: &str,
+    token: &str,
+    id: &str,
+    response: &EventResponse,
+) -> Result<(), String> {
+    let action = match response {
+        EventResponse::Accepted => "accept",
+        EventResponse::TentativelyAccepted => "tentativelyAccept",
+        EventResponse::Declined => "decline",
+        EventResponse::NotResponded => return Err("Cannot respond with Not Responded".to_string()),
+        EventResponse::Organizer => return Err("Cannot respond to your own event".to_string()),
+    };
+
+    let response = client
+        .post(format!("{}/{}/{}", events_url, id, action))
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Content-Type", "application/json")
+        .body(r#"{"sendResponse": true}"#)
+        .send()
+        .await
+        .map_err(|error| error.to_string())?;
+
+    match response.status().is_success() {
+        true => Ok(()),
+        false => Err(format!("Graph returned {}", response.status())),
+    }
+}
+
+// e.g. https://graph.microsoft.com/v1.0/me/calendarView -> .../me/calendars/{id}/calendarView
+pub fn calendar_view_url(base_url: &str, calendar_id: Option<&str>) -> String {
+    match (calendar_id, base_url.rsplit_once('/')) {
+        (Some(id), Some((me, view))) => format!("{me}/calendars/{id}/{view}"),
+        _ => base_url.to_string(),
+    }
+}
+
+// One event with every field, for when polls only $select a few.
+pub async fn fetch_event(
+    client: &Client,
+    url: &str,
+    token: &str,
+) -> Result<CalendarEvent, FetchError> {
+    let response = client
+        .get(url)
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await?;
+    let value = FetchError::check(response)?.json::<Value>().await?;
+    to_calendar_event(&value).map_err(|error| FetchError::Failed(error.to_string()))
+}
+
+// Every calendar the account can see, including ones shared with it.
+pub async fn list_calendars(
+    client: &Client,
+    calendars_url: &str,
+    token: &str,
+) -> Result<Vec<Calendar>, FetchError> {
+    let mut calendars = Vec::new();
+    let mut next_link = Some(calendars_url.to_string());
+
+    while let Some(url) = next_link {
+        let response = client
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", token))
+            .send()
+            .await?;
+        let page = FetchError::check(response)?.json::<CalendarsRoot>().await?;
+        calendars.extend(page.value);
+        next_link = page.odata_next_link;
+    }
+    Ok(calendars)
+}
+
+fn query_window(window: &Window) -> (String, String) {
+    (
+        window.start.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        window.end.format("%Y-%m-%dT%H:%M:%S").to_string(),
+    )
+}
+
+// Follows @odata.nextLink to the final page's @odata.deltaLink. Ok(None) means the deltaLink has
+// expired and a full sync is needed. Unlike a plain listing, a partial delta can't be kept, since
+// resuming needs the link from the last page.
+async fn fetch_delta(
+    client: &Client,
+    url: String,
+    token: &str,
+) -> Result<Option<(Vec<DeltaValue>, String)>, FetchError> {
+    let mut changes = Vec::new();
+    let mut next_link = url;
+
+    loop {
+        let response = client
+            .get(&next_link)
+            .header("Authorization", format!("Bearer {}", token))
+            .send()
+            .await?;
+        if response.status() == StatusCode::GONE {
+            return Ok(None);
+        }
+        let page = FetchError::check(response)?.json::<DeltaRoot>().await?;
+        changes.extend(page.value);
+
+        match (page.odata_next_link, page.odata_delta_link) {
+            (Some(next), _) => next_link = next,
+            (None, Some(delta_link)) => return Ok(Some((changes, delta_link))),
+            (None, None) => {
+                return Err(FetchError::Failed(
+                    "Graph returned no deltaLink".to_string(),
+                ))
             }
+        }
+    }
+}
+
+pub(crate) fn to_calendar_event(v: &Value) -> Result<CalendarEvent, BackendError> {
+    let malformed = |reason: &str| BackendError::MalformedEvent {
+        subject: v.subject.clone(),
+        reason: reason.to_string(),
+    };
+
+    let id = v.id.clone().ok_or_else(|| malformed("no ID"))?;
+    // Graph leaves these out of some all-day and malformed entries.
+    let start_time = v
+        .start
+        .date_time
+        .as_deref()
+        .ok_or_else(|| malformed("no start time"))
+        .and_then(|date_time| {
+            parse_graph_time(date_time, v.start.time_zone.as_deref())
+                .ok_or_else(|| malformed("invalid start time"))
+        })?;
+    let end_time = v
+        .end
+        .date_time
+        .as_deref()
+        .ok_or_else(|| malformed("no end time"))
+        .and_then(|date_time| {
+            parse_graph_time(date_time, v.end.time_zone.as_deref())
+                .ok_or_else(|| malformed("invalid end time"))
+        })?;
+
+    let is_cancelled = v.is_cancelled;
+    let is_all_day = v.is_all_day;
+    // Private events and some room bookings come back without these.
+    let organizer = v.organizer.email_address.name.clone().unwrap_or_default();
+    let subject = v.subject.clone().unwrap_or_default();
+
+    // onlineMeetingUrl is often empty even for Teams meetings, and invites for other services
+    // only carry the link in their text.
+    let join_url = v
+        .online_meeting
+        .as_ref()
+        .and_then(|meeting| meeting.join_url.clone())
+        .map(|url| (url, LinkSource::JoinUrl));
+    let online_meeting_url = v
+        .online_meeting_url
+        .clone()
+        .map(|url| (url, LinkSource::OnlineMeetingUrl));
+    let body_link = v
+        .body
+        .as_ref()
+        .and_then(|body| body.content.as_deref())
+        .and_then(find_join_link)
+        .map(|url| (url, LinkSource::Body));
+    let teams_meeting = [join_url, online_meeting_url, body_link]
+        .into_iter()
+        .flatten()
+        .find(|(url, _)| !url.is_empty())
+        .map(|(url, source)| TeamsMeeting { url, source });
+
+    let response: Option<EventResponse> = match v.response_status.response.as_ref() {
+        Some(status) => match status.as_ref() {
+            "accepted" => Some(EventResponse::Accepted),
+            "tentativelyAccepted" => Some(EventResponse::TentativelyAccepted),
+            "declined" => Some(EventResponse::Declined),
+            "notResponded" => Some(EventResponse::NotResponded),
+            "organizer" => Some(EventResponse::Organizer),
+            // "none", or anything Graph adds later.
+            _ => None,
+        },
+        None => None,
+    };
+
+    let location = v
+        .location
+        .clone()
+        .unwrap_or_default()
+        .display_name
+        .unwrap_or_default();
+
+    let (body, raw_body) = match v.body.clone().unwrap_or_default() {
+        Body {
+            content: Some(content),
+            content_type: Some(content_type),
+        } if content_type.eq_ignore_ascii_case("html") => (html_to_text(&content), Some(content)),
+        Body {
+            content: Some(content),
+            ..
+        } => (content, None),
+        _ => (v.body_preview.clone().unwrap_or_default(), None),
+    };
+
+    let attendees = v
+        .attendees
+        .iter()
+        .map(|attendee| EventAttendee {
+            name: attendee
+                .email_address
+                .name
+                .clone()
+                .or(attendee.email_address.address.clone())
+                .unwrap_or_default(),
+            response: match attendee.status.response.as_deref() {
+                Some("accepted" | "organizer") => AttendeeResponse::Accepted,
+                Some("tentativelyAccepted") => AttendeeResponse::Tentative,
+                Some("declined") => AttendeeResponse::Declined,
+                _ => AttendeeResponse::NoResponse,
+            },
+        })
+        .collect();
+
+    // calendarView returns occurrences, which only carry the pattern on their series master.
+    let recurrence = match v.type_field.as_deref() {
+        Some("occurrence" | "exception" | "seriesMaster") => Some(v.recurrence.as_ref().map_or(
+            RecurrenceSummary::default(),
+            |recurrence| {
+                RecurrenceSummary::new(
+                    recurrence.pattern.type_field.as_deref().unwrap_or_default(),
+                    recurrence.pattern.interval,
+                )
+            },
+        )),
+        _ => None,
+    };
+
+    Ok(CalendarEvent {
+        id,
+        body,
+        raw_body,
+        i_cal_uid: v.i_cal_uid.clone(),
+        also_in: Vec::new(),
+        location,
+        is_cancelled,
+        is_all_day,
+        start_time,
+        end_time,
+        subject,
+        organizer,
+        teams_meeting,
+        web_link: v.web_link.clone(),
+        response,
+        recurrence,
+        attendees,
+        importance: match v.importance.as_deref() {
+            Some("high") => Importance::High,
+            Some("low") => Importance::Low,
+            _ => Importance::Normal,
+        },
+        is_private: matches!(v.sensitivity.as_deref(), Some("private" | "confidential")),
+        categories: v.categories.iter().flatten().cloned().collect(),
+        show_as: match v.show_as.as_deref() {
+            Some("free") => ShowAs::Free,
+            Some("tentative") => ShowAs::Tentative,
+            Some("oof") => ShowAs::OutOfOffice,
+            Some("workingElsewhere") => ShowAs::WorkingElsewhere,
+            _ => ShowAs::Busy,
+        },
+        calendar_name: None,
+        account: None,
+    })
+}
+
+fn parse_graph_time(date_time: &str, time_zone: Option<&str>) -> Option<DateTime<Utc>> {
+    let naive = NaiveDateTime::parse_from_str(date_time, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+
+    // Fall back to UTC when the zone is absent or unrecognized.
+    match time_zone.and_then(to_tz) {
+        Some(tz) => tz
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|dt| dt.with_timezone(&Utc)),
+        None => Some(Utc.from_utc_datetime(&naive)),
+    }
+}
+
+// Graph reports Windows time zone names unless told otherwise, so map the
+// common ones onto IANA zones. IANA names are accepted as-is.
+pub fn to_tz(time_zone: &str) -> Option<Tz> {
+    use chrono_tz::{America, Asia, Atlantic, Australia, Europe, Pacific};
+
+    let tz = match time_zone {
+        "UTC" | "Coordinated Universal Time" => Tz::UTC,
+        "GMT Standard Time" => Europe::London,
+        "Greenwich Standard Time" => Atlantic::Reykjavik,
+        "W. Europe Standard Time" => Europe::Berlin,
+        "Romance Standard Time" => Europe::Paris,
+        "Central Europe Standard Time" => Europe::Budapest,
+        "Central European Standard Time" => Europe::Warsaw,
+        "E. Europe Standard Time" => Europe::Chisinau,
+        "FLE Standard Time" => Europe::Kiev,
+        "GTB Standard Time" => Europe::Bucharest,
+        "Russian Standard Time" => Europe::Moscow,
+        "Israel Standard Time" => Asia::Jerusalem,
+        "Arabian Standard Time" => Asia::Dubai,
+        "India Standard Time" => Asia::Kolkata,
+        "Singapore Standard Time" => Asia::Singapore,
+        "China Standard Time" => Asia::Shanghai,
+        "Korea Standard Time" => Asia::Seoul,
+        "Tokyo Standard Time" => Asia::Tokyo,
+        "AUS Eastern Standard Time" => Australia::Sydney,
+        "New Zealand Standard Time" => Pacific::Auckland,
+        "Hawaiian Standard Time" => Pacific::Honolulu,
+        "Alaskan Standard Time" => America::Anchorage,
+        "Pacific Standard Time" => America::Los_Angeles,
+        "US Mountain Standard Time" => America::Phoenix,
+        "Mountain Standard Time" => America::Denver,
+        "Central Standard Time" => America::Chicago,
+        "Eastern Standard Time" => America::New_York,
+        "Atlantic Standard Time" => America::Halifax,
+        "E. South America Standard Time" => America::Sao_Paulo,
+        _ => return time_zone.parse().ok(),
+    };
+    Some(tz)
+}
+
+// Graph bodies are usually whole HTML documents. Keeps the line and paragraph breaks, drops the
+// markup along with style and script contents, and collapses the source's own whitespace.
+fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    // Element whose contents aren't text, until its closing tag.
+    let mut skipping: Option<String> = None;
+
+    while let Some(start) = rest.find('<') {
+        if skipping.is_none() {
+            push_text(&mut text, &rest[..start]);
+        }
+        rest = &rest[start..];
+
+        // Comments, including Outlook's conditional ones, may contain '>'.
+        if rest.starts_with("<!--") {
+            rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+            continue;
+        }
+        let Some(end) = rest.find('>') else {
+            rest = "";
+            break;
         };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if let Some(skipped) = &skipping {
+            if closing && name == *skipped {
+                skipping = None;
+            }
+            continue;
+        }
+        match name.as_str() {
+            "style" | "script" | "head" | "title" if !closing => skipping = Some(name),
+            "li" if !closing => text.push_str("\n• "),
+            "br" | "p" | "div" | "tr" | "ul" | "ol" | "table" | "h1" | "h2" | "h3" | "h4"
+            | "h5" | "h6" => text.push('\n'),
+            _ => (),
+        }
+    }
+    if skipping.is_none() {
+        push_text(&mut text, rest);
+    }
 
-        sleep(Duration::from_millis(16)).await;
+    // At most one blank line between paragraphs.
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if !(line.is_empty() && lines.last().is_none_or(|last| last.is_empty())) {
+            lines.push(line);
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
+
+// Text between tags, where any run of whitespace (line breaks included) is a single space.
+fn push_text(text: &mut String, html: &str) {
+    for c in decode_entities(html).chars() {
+        match c.is_whitespace() {
+            false => text.push(c),
+            true if !text.is_empty() && !text.ends_with(char::is_whitespace) => text.push(' '),
+            true => (),
+        }
+    }
+}
+
+// Named entities common in mail bodies, and numeric ones. Anything else is left as it is.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .map(|end| &rest[1..1 + end]);
+        let c = entity.and_then(|entity| match entity {
+            "nbsp" => Some(' '),
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|code| code.parse().ok()))
+                .and_then(char::from_u32),
+        });
+        match (entity, c) {
+            (Some(entity), Some(c)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
     }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[derive(Debug)]
+pub enum EventCommand {
+    Add(Box<CalendarEvent>),
+    Remove(String),
 }
+
 #[derive(Debug, Default, Clone)]
 pub struct TeamsMeeting {
     pub url: String,
+    pub source: LinkSource,
+}
+
+// Where a meeting's join link was found.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LinkSource {
+    // The provider's own join link field.
+    #[default]
+    JoinUrl,
+    // Graph's older onlineMeetingUrl.
+    OnlineMeetingUrl,
+    // Picked out of the invite's description.
+    Body,
 }
 
-#[derive(Debug, Default)]
+// Links to these are the way into a meeting. Zoom's may be on a company subdomain.
+const JOIN_LINK_HOSTS: [&str; 3] = [
+    "teams.microsoft.com/l/meetup-join/",
+    "meet.google.com/",
+    "zoom.us/j/",
+];
+
+// The first meeting link in an invite's text or HTML.
+pub fn find_join_link(text: &str) -> Option<String> {
+    text.match_indices("https://").find_map(|(start, _)| {
+        let end = text[start..]
+            .find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '<' | '>'))
+            .map_or(text.len(), |end| start + end);
+        let url = &text[start..end];
+        JOIN_LINK_HOSTS
+            .iter()
+            .any(|host| url.contains(host))
+            .then(|| url.replace("&amp;", "&"))
+    })
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct CalendarEvent {
     pub id: String,
     pub body: String,
+    // The HTML the body was converted from, if it was.
+    pub raw_body: Option<String>,
+    // Shared by every copy of a meeting, whichever calendar it's read from.
+    pub i_cal_uid: Option<String>,
+    // Other calendars the same meeting was found in, merged into this one.
+    pub also_in: Vec<String>,
     pub location: String,
     pub is_cancelled: bool,
+    pub is_all_day: bool,
     pub end_time: DateTime<Utc>,
     pub start_time: DateTime<Utc>,
     pub organizer: String,
     pub subject: String,
     pub teams_meeting: Option<TeamsMeeting>,
+    pub web_link: Option<String>,
     pub response: Option<EventResponse>,
+    pub recurrence: Option<RecurrenceSummary>,
+    pub attendees: Vec<EventAttendee>,
+    pub importance: Importance,
+    // Private or confidential; providers hide most details of these from others.
+    pub is_private: bool,
+    pub categories: Vec<String>,
+    pub show_as: ShowAs,
+    // Which calendar it came from, when several are shown together.
+    pub calendar_name: Option<String>,
+    // Which of the [[accounts]] it came from.
+    pub account: Option<String>,
+}
+
+// How the time shows on the owner's free/busy schedule.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ShowAs {
+    Free,
+    Tentative,
+    #[default]
+    Busy,
+    OutOfOffice,
+    WorkingElsewhere,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Importance {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+#[derive(Debug, Clone)]
+pub struct EventAttendee {
+    pub name: String,
+    pub response: AttendeeResponse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttendeeResponse {
+    Accepted,
+    Tentative,
+    Declined,
+    NoResponse,
+}
+
+impl fmt::Display for AttendeeResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AttendeeResponse::Accepted => write!(f, "accepted"),
+            AttendeeResponse::Tentative => write!(f, "tentative"),
+            AttendeeResponse::Declined => write!(f, "declined"),
+            AttendeeResponse::NoResponse => write!(f, "no response"),
+        }
+    }
+}
+
+// How often a repeating event recurs, where the provider says.
+#[derive(Debug, Default, Clone)]
+pub struct RecurrenceSummary {
+    // day, week, month or year.
+    pub unit: Option<&'static str>,
+    pub interval: i64,
+}
+
+impl RecurrenceSummary {
+    // Accepts Graph's pattern types (e.g. "absoluteMonthly") as well as RRULE FREQ values.
+    pub fn new(frequency: &str, interval: i64) -> Self {
+        let unit = match frequency.to_lowercase() {
+            f if f.ends_with("daily") => Some("day"),
+            f if f.ends_with("weekly") => Some("week"),
+            f if f.ends_with("monthly") => Some("month"),
+            f if f.ends_with("yearly") => Some("year"),
+            _ => None,
+        };
+        Self {
+            unit,
+            interval: interval.max(1),
+        }
+    }
+}
+
+impl fmt::Display for RecurrenceSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.unit, self.interval) {
+            (None, _) => write!(f, "↻"),
+            (Some("day"), 1) => write!(f, "↻ daily"),
+            (Some(unit), 1) => write!(f, "↻ {unit}ly"),
+            (Some(unit), interval) => write!(f, "↻ every {interval} {unit}s"),
+        }
+    }
+}
+
+impl CalendarEvent {
+    // How much there is to show, for keeping the fuller copy of a duplicated meeting.
+    pub fn completeness(&self) -> usize {
+        [
+            !self.body.is_empty(),
+            !self.location.is_empty(),
+            self.teams_meeting.is_some(),
+            self.response.is_some(),
+        ]
+        .into_iter()
+        .filter(|present| *present)
+        .count()
+            + self.attendees.len()
+    }
+
+    // The calendar, or else the account, the event was read from.
+    pub fn source(&self) -> Option<&str> {
+        self.calendar_name.as_deref().or(self.account.as_deref())
+    }
+
+    // Prefer the join URL, falling back to the provider's web link for the event.
+    pub fn meeting_url(&self) -> Option<&str> {
+        self.teams_meeting
+            .as_ref()
+            .map(|meeting| meeting.url.as_str())
+            .filter(|url| !url.is_empty())
+            .or(self.web_link.as_deref())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum EventResponse {
     Accepted,
+    TentativelyAccepted,
+    Declined,
     NotResponded,
+    // The user's own meeting, which has nothing to respond to.
+    Organizer,
 }
 
 impl fmt::Display for EventResponse {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             EventResponse::Accepted => write!(f, "Accepted"),
+            EventResponse::TentativelyAccepted => write!(f, "Tentative"),
+            EventResponse::Declined => write!(f, "Declined"),
             EventResponse::NotResponded => write!(f, "Not Responded"),
+            EventResponse::Organizer => write!(f, "Organizer"),
         }
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Calendar {
+    pub id: String,
+    pub name: String,
+    #[serde(default, rename = "isDefaultCalendar")]
+    pub is_default: bool,
+}
+
 #[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Root {
-    #[serde(rename = "@odata.context")]
-    pub odata_context: Option<String>,
-    pub value: Vec<Value>,
+pub struct CalendarsRoot {
+    pub value: Vec<Calendar>,
     #[serde(rename = "@odata.nextLink")]
     pub odata_next_link: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct DeltaRoot {
+    pub value: Vec<DeltaValue>,
+    #[serde(rename = "@odata.nextLink")]
+    pub odata_next_link: Option<String>,
+    #[serde(rename = "@odata.deltaLink")]
+    pub odata_delta_link: Option<String>,
+}
+
+// Deleted events come back as just an ID and an @removed annotation.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DeltaValue {
+    Removed {
+        id: String,
+        #[serde(rename = "@removed")]
+        removed: Removed,
+    },
+    Changed(Box<Value>),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Removed {
+    pub reason: Option<String>,
+}
+
+// Any field can be missing, since [outlook] select trims what Graph returns.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct Value {
     #[serde(rename = "@odata.etag")]
     pub odata_etag: Option<String>,
@@ -227,7 +893,7 @@ pub struct Value {
     pub calendar_odata_navigation_link: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ResponseStatus {
     pub response: Option<String>,
@@ -241,14 +907,14 @@ pub struct Body {
     pub content: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Start {
     pub date_time: Option<String>,
     pub time_zone: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct End {
     pub date_time: Option<String>,
@@ -344,13 +1010,13 @@ pub struct EmailAddress {
     pub address: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Organizer {
     pub email_address: EmailAddress2,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EmailAddress2 {
     pub name: Option<String>,
@@ -363,3 +1029,131 @@ pub struct OnlineMeeting {
     pub join_url: Option<String>,
     pub quick_dial: Option<Option<String>>,
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    // A Graph event with only the required fields, plus whatever the test sets.
+    pub(crate) fn value(fields: serde_json::Value) -> Value {
+        let mut value = json!({
+            "id": "AAMk1",
+            "iCalUId": "040000008200E00074C5B7101A82E008",
+            "subject": "Design review",
+            "categories": [],
+            "reminderMinutesBeforeStart": 15,
+            "isReminderOn": true,
+            "hasAttachments": false,
+            "isAllDay": false,
+            "isCancelled": false,
+            "isOrganizer": false,
+            "responseRequested": true,
+            "isOnlineMeeting": false,
+            "allowNewTimeProposals": true,
+            "isDraft": false,
+            "hideAttendees": false,
+            "responseStatus": {},
+            "start": { "dateTime": "2024-03-01T09:00:00.0000000", "timeZone": "UTC" },
+            "end": { "dateTime": "2024-03-01T10:00:00.0000000", "timeZone": "UTC" },
+            "locations": [],
+            "attendees": [],
+            "organizer": { "emailAddress": { "name": "Alex Kim" } },
+        });
+        for (key, field) in fields.as_object().unwrap() {
+            value[key] = field.clone();
+        }
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn windows_zone_names_map_onto_iana_zones() {
+        use chrono_tz::{America, Europe};
+
+        assert_eq!(to_tz("W. Europe Standard Time"), Some(Europe::Berlin));
+        assert_eq!(to_tz("Pacific Standard Time"), Some(America::Los_Angeles));
+        assert_eq!(to_tz("Europe/Paris"), Some(Europe::Paris));
+        assert_eq!(to_tz("Mars Standard Time"), None);
+    }
+
+    #[test]
+    fn graph_times_follow_daylight_saving() {
+        let zone = Some("Pacific Standard Time");
+        // PST is UTC-8 in winter and PDT UTC-7 in summer, for the same wall-clock time.
+        assert_eq!(
+            parse_graph_time("2024-01-15T09:00:00.0000000", zone),
+            Some(Utc.with_ymd_and_hms(2024, 1, 15, 17, 0, 0).unwrap())
+        );
+        assert_eq!(
+            parse_graph_time("2024-07-15T09:00:00.0000000", zone),
+            Some(Utc.with_ymd_and_hms(2024, 7, 15, 16, 0, 0).unwrap())
+        );
+        // Unknown zones are read as UTC.
+        assert_eq!(
+            parse_graph_time("2024-07-15T09:00:00", Some("Nowhere")),
+            Some(Utc.with_ymd_and_hms(2024, 7, 15, 9, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn delta_pages_with_only_selected_fields_parse() {
+        let page = json!({
+            "value": [
+                {
+                    "id": "AAMk1",
+                    "iCalUId": "040000008200E00074C5B7101A82E008",
+                    "subject": "Design review",
+                    "showAs": "busy",
+                    "isOnlineMeeting": false,
+                    "isAllDay": false,
+                    "isCancelled": false,
+                    "start": { "dateTime": "2024-03-01T09:00:00.0000000", "timeZone": "UTC" },
+                    "end": { "dateTime": "2024-03-01T10:00:00.0000000", "timeZone": "UTC" },
+                },
+                { "id": "AAMk2", "@removed": { "reason": "deleted" } },
+            ],
+            "@odata.deltaLink": "https://graph.microsoft.com/v1.0/me/calendarView/delta?$deltatoken=x",
+        });
+        let page = serde_json::from_value::<DeltaRoot>(page).unwrap();
+
+        let [DeltaValue::Changed(value), DeltaValue::Removed { id, .. }] = &page.value[..] else {
+            panic!("changes parsed as the wrong kinds");
+        };
+        assert_eq!(id, "AAMk2");
+        let event = to_calendar_event(value).unwrap();
+        assert_eq!(event.subject, "Design review");
+        assert_eq!(event.organizer, "");
+    }
+
+    #[test]
+    fn events_without_a_time_are_skipped() {
+        let no_start = value(json!({ "start": { "dateTime": null, "timeZone": "UTC" } }));
+        let no_end = value(json!({ "end": { "timeZone": "UTC" } }));
+        for value in [no_start, no_end] {
+            assert!(matches!(
+                to_calendar_event(&value),
+                Err(BackendError::MalformedEvent { .. })
+            ));
+        }
+        assert!(to_calendar_event(&value(json!({}))).is_ok());
+    }
+
+    #[test]
+    fn query_window_spans_the_whole_window() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 9, 30, 15).unwrap()
+            + chrono::Duration::milliseconds(250);
+        let window = Window {
+            start,
+            end: start + chrono::Duration::days(7),
+        };
+        // Graph wants no offset or fraction; both ends are UTC.
+        assert_eq!(
+            query_window(&window),
+            (
+                "2024-03-01T09:30:15".to_string(),
+                "2024-03-08T09:30:15".to_string()
+            )
+        );
+    }
+}
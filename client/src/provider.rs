@@ -0,0 +1,304 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    future::Future,
+    sync::mpsc::Sender,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Days, Utc};
+use reqwest::{header::RETRY_AFTER, Response, StatusCode};
+use serde::Deserialize;
+use tokio::{sync::watch, time::sleep};
+use tracing::{debug, warn};
+
+use crate::{
+    app::Config,
+    backend::{AppStatus, BackendError},
+    outlook::{CalendarEvent, EventCommand},
+};
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    #[default]
+    Outlook,
+    Google,
+    #[serde(rename = "caldav")]
+    CalDav,
+    // No account, only the configured ics_urls.
+    Ics,
+    // Canned events, for demos and working on the UI without an account.
+    Mock,
+}
+
+pub struct Window {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl Window {
+    pub fn from_now(limit_days: u64) -> Self {
+        let start = Utc::now();
+        let end = start
+            .checked_add_days(Days::new(limit_days))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        Self { start, end }
+    }
+}
+
+pub trait CalendarProvider {
+    // Err when the fetch failed, so stale events aren't mistaken for removals.
+    fn fetch(
+        &self,
+        window: &Window,
+    ) -> impl Future<Output = Result<Vec<CalendarEvent>, FetchError>> + Send;
+}
+
+#[derive(Debug)]
+pub enum FetchError {
+    // 429, with how long the server asked us to wait if it said.
+    Throttled(Option<Duration>),
+    // 5xx, worth backing off from until the service recovers.
+    Unavailable(StatusCode),
+    // No response within network.timeout_seconds.
+    TimedOut,
+    // 401, left after trying to renew the token.
+    Unauthorized,
+    // Network errors, other statuses and unreadable responses.
+    Failed(String),
+}
+
+impl FetchError {
+    // Passes successful responses through and classifies the rest.
+    pub fn check(response: Response) -> Result<Response, FetchError> {
+        let status = response.status();
+        match status {
+            status if status.is_success() => Ok(response),
+            StatusCode::UNAUTHORIZED => Err(FetchError::Unauthorized),
+            StatusCode::TOO_MANY_REQUESTS => {
+                // Only the delay-seconds form; an HTTP date falls back to backing off.
+                let retry_after = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.trim().parse::<u64>().ok())
+                    .map(Duration::from_secs);
+                Err(FetchError::Throttled(retry_after))
+            }
+            status if status.is_server_error() => Err(FetchError::Unavailable(status)),
+            status => Err(FetchError::Failed(format!("server returned {status}"))),
+        }
+    }
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(error: reqwest::Error) -> Self {
+        match error.is_timeout() {
+            true => FetchError::TimedOut,
+            false => FetchError::Failed(error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FetchError::Throttled(_) => write!(f, "rate limited by the server"),
+            FetchError::Unavailable(status) => write!(f, "server unavailable ({status})"),
+            FetchError::TimedOut => write!(f, "request timed out"),
+            FetchError::Unauthorized => write!(f, "sign-in expired"),
+            FetchError::Failed(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+// Exponential from 5 seconds up to 10 minutes, plus up to a quarter again so clients that failed
+// together don't retry together.
+fn backoff(failures: u32) -> Duration {
+    let delay = Duration::from_secs(5)
+        .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+        .min(Duration::from_secs(600));
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.subsec_nanos());
+    delay + delay.mul_f64((nanos % 1000) as f64 / 4000.0)
+}
+
+// Consecutive failures before the user is told the events on screen may be stale.
+const STALE_AFTER_FAILURES: u32 = 3;
+
+pub async fn refresh<P: CalendarProvider>(
+    provider: P,
+    event_tx: Sender<EventCommand>,
+    status_tx: Sender<AppStatus>,
+    error_tx: Sender<BackendError>,
+    mut refresh_rx: watch::Receiver<()>,
+    config: &'static Config,
+) {
+    // IDs seen on the previous poll, diffed against each new response.
+    let mut snapshot: HashSet<String> = HashSet::new();
+    let period = Duration::from_secs(config.refresh_period_seconds as u64);
+    let mut failures = 0;
+
+    // Fetch on startup, then wait between polls.
+    loop {
+        // Recomputed every poll so the horizon slides forward.
+        let window = Window::from_now(config.limit_days);
+
+        let delay = match provider.fetch(&window).await {
+            Ok(calendar_events) => {
+                let current = calendar_events
+                    .iter()
+                    .filter(|e| !e.is_cancelled)
+                    .map(|e| e.id.clone())
+                    .collect::<HashSet<_>>();
+
+                // Anything cancelled or no longer returned by the provider is removed.
+                let removed = snapshot.difference(&current).cloned().chain(
+                    calendar_events
+                        .iter()
+                        .filter(|e| e.is_cancelled)
+                        .map(|e| e.id.clone()),
+                );
+                for id in removed {
+                    event_tx
+                        .send(EventCommand::Remove(id))
+                        .expect("ERROR: Could not send message to main thread");
+                }
+
+                // Meetings already underway stay listed until they end.
+                for event in calendar_events
+                    .into_iter()
+                    .filter(|e| !e.is_cancelled && e.end_time > Utc::now())
+                {
+                    event_tx
+                        .send(EventCommand::Add(Box::new(event)))
+                        .expect("ERROR: Could not send message to main thread");
+                }
+
+                debug!(events = current.len(), "fetched events");
+                snapshot = current;
+                failures = 0;
+                _ = status_tx.send(AppStatus::Ready);
+                period
+            }
+            Err(error) => {
+                failures += 1;
+                warn!(failures, "fetch failed: {error}");
+                _ = status_tx.send(AppStatus::Error(format!(
+                    "Could not fetch events ({error}), retrying"
+                )));
+                // Nothing will arrive until the user signs in again, so say so once rather than
+                // leaving the events to go stale.
+                if failures == 1 && matches!(error, FetchError::Unauthorized) {
+                    _ = error_tx.send(BackendError::SignInExpired);
+                }
+                // A hung connection is worth flagging straight away, since it held up this poll
+                // for the whole timeout already.
+                if failures == STALE_AFTER_FAILURES || matches!(error, FetchError::TimedOut) {
+                    _ = error_tx.send(BackendError::FetchFailed {
+                        failures,
+                        reason: error.to_string(),
+                    });
+                }
+
+                // Throttling and outages get more breathing room than the usual schedule.
+                match error {
+                    FetchError::Throttled(Some(retry_after)) => retry_after.max(period),
+                    FetchError::Throttled(None) | FetchError::Unavailable(_) => {
+                        backoff(failures).max(period)
+                    }
+                    FetchError::TimedOut | FetchError::Unauthorized | FetchError::Failed(_) => {
+                        period
+                    }
+                }
+            }
+        };
+
+        // A manual refresh cuts the wait short.
+        tokio::select! {
+            _ = sleep(delay) => (),
+            Ok(()) = refresh_rx.changed() => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::channel,
+    };
+
+    use reqwest::Client;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::{app::tests::config, mock};
+
+    // Hangs on its first fetch, against a server that accepts but never answers, then recovers.
+    struct Stalling {
+        client: Client,
+        url: String,
+        stalled: AtomicBool,
+    }
+
+    impl CalendarProvider for Stalling {
+        async fn fetch(&self, _: &Window) -> Result<Vec<CalendarEvent>, FetchError> {
+            if !self.stalled.swap(true, Ordering::SeqCst) {
+                self.client.get(&self.url).send().await?;
+            }
+            Ok(mock::events(Utc::now()))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn refresh_recovers_after_a_stalled_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+
+        let provider = Stalling {
+            client: Client::builder()
+                .timeout(Duration::from_millis(200))
+                .build()
+                .unwrap(),
+            url,
+            stalled: AtomicBool::new(false),
+        };
+        let (event_tx, event_rx) = channel();
+        let (status_tx, _status_rx) = channel();
+        let (error_tx, error_rx) = channel();
+        let (refresh_tx, refresh_rx) = watch::channel(());
+        tokio::spawn(refresh(
+            provider,
+            event_tx,
+            status_tx,
+            error_tx,
+            refresh_rx,
+            config(),
+        ));
+
+        // Flagged as soon as it times out, and the loop carries on to the next poll.
+        let error =
+            tokio::task::spawn_blocking(move || error_rx.recv_timeout(Duration::from_secs(5)))
+                .await
+                .unwrap();
+        assert!(matches!(
+            error,
+            Ok(BackendError::FetchFailed { failures: 1, .. })
+        ));
+        refresh_tx.send(()).unwrap();
+        let command =
+            tokio::task::spawn_blocking(move || event_rx.recv_timeout(Duration::from_secs(5)))
+                .await
+                .unwrap();
+        assert!(matches!(command, Ok(EventCommand::Add(_))));
+    }
+}
@@ -0,0 +1,79 @@
+use std::{fs, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::app::{config_dir, Focus};
+
+// What the UI was showing at exit, restored on the next start. Written by the app rather than
+// the user, so it's kept out of config.toml.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    pub view: View,
+    pub filter: String,
+    // The calendar picked with cycle_calendar, if any.
+    pub calendar_id: Option<String>,
+    pub account_filter: Option<String>,
+    pub hide_outside_hours: bool,
+    // Palette picked with cycle_theme, and the configured one it replaced. Editing theme in the
+    // config wins over a pick made before the edit.
+    pub theme: Option<usize>,
+    pub configured_theme: Option<usize>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum View {
+    #[default]
+    Table,
+    Week,
+    FreeBusy,
+}
+
+impl View {
+    pub fn focus(&self) -> Focus {
+        match self {
+            View::Table => Focus::Table,
+            View::Week => Focus::Week,
+            View::FreeBusy => Focus::FreeBusy,
+        }
+    }
+}
+
+// Popups and the detail pane are about one moment, so they come back as the table.
+impl From<Focus> for View {
+    fn from(focus: Focus) -> Self {
+        match focus {
+            Focus::Week => View::Week,
+            Focus::FreeBusy => View::FreeBusy,
+            _ => View::Table,
+        }
+    }
+}
+
+fn state_path() -> PathBuf {
+    config_dir().join("state.json")
+}
+
+impl State {
+    // A missing or unreadable file just means starting afresh.
+    pub fn load() -> Self {
+        fs::read_to_string(state_path())
+            .ok()
+            .and_then(|file| serde_json::from_str(&file).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(self)
+            .map_err(|error| error.to_string())
+            .and_then(|json| {
+                fs::create_dir_all(config_dir()).map_err(|error| error.to_string())?;
+                fs::write(state_path(), json).map_err(|error| error.to_string())
+            });
+        if let Err(error) = result {
+            warn!("could not save the UI state: {error}");
+        }
+    }
+}
@@ -1,11 +1,21 @@
-use chrono::{DateTime, Local};
+use std::{collections::HashMap, str::FromStr};
+
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDate, NaiveTime, Utc};
+use chrono_tz::Tz;
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table},
+    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState, Wrap},
 };
+use serde::Deserialize;
 use style::palette::tailwind;
 
-use crate::{outlook::EventResponse, App};
+use crate::{
+    app::{Column, SortBy, TimeFormat},
+    backend::{AppStatus, AuthError},
+    keybindings::Action,
+    outlook::{AttendeeResponse, CalendarEvent, EventAttendee, Importance, LinkSource, ShowAs},
+    App, CONFIG,
+};
 
 pub const PALETTES: [tailwind::Palette; 9] = [
     tailwind::BLUE,
@@ -19,6 +29,7 @@ pub const PALETTES: [tailwind::Palette; 9] = [
     tailwind::SKY,
 ];
 
+#[derive(Debug, Clone, Copy)]
 pub struct TableColors {
     buffer_bg: Color,
     header_bg: Color,
@@ -43,6 +54,477 @@ impl TableColors {
             // footer_border_color: color.c400,
         }
     }
+
+    // The terminal's own colors everywhere; no_color relies on attributes instead.
+    pub fn monochrome() -> Self {
+        Self {
+            buffer_bg: Color::Reset,
+            header_bg: Color::Reset,
+            header_fg: Color::Reset,
+            row_fg: Color::Reset,
+            selected_style_fg: Color::Reset,
+            normal_row_color: Color::Reset,
+            alt_row_color: Color::Reset,
+        }
+    }
+
+    // Background of the i-th event of a day, striped unless alternating_rows is off.
+    pub fn row_color(&self, i: usize) -> Color {
+        match CONFIG.get().unwrap().alternating_rows && i % 2 == 1 {
+            true => self.alt_row_color,
+            false => self.normal_row_color,
+        }
+    }
+}
+
+// How the selected row stands out. Reversed swaps its colors, which some palettes make garish.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SelectionStyle {
+    #[default]
+    Reversed,
+    Bold,
+    // Filled with selected_style_fg.
+    Bg,
+}
+
+impl SelectionStyle {
+    pub fn style(&self, colors: &TableColors) -> Style {
+        match self {
+            SelectionStyle::Reversed => Style::default()
+                .add_modifier(Modifier::REVERSED)
+                .fg(colors.selected_style_fg),
+            SelectionStyle::Bold => Style::default().bold().fg(colors.selected_style_fg),
+            SelectionStyle::Bg => Style::default()
+                .fg(colors.buffer_bg)
+                .bg(colors.selected_style_fg),
+        }
+    }
+}
+
+// Either an index into PALETTES or a [theme] table of explicit colors.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "ThemeConfig")]
+pub enum Theme {
+    Palette(usize),
+    Custom(TableColors),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ThemeConfig {
+    Palette(usize),
+    Custom(HashMap<String, String>),
+}
+
+impl TryFrom<ThemeConfig> for Theme {
+    type Error = String;
+
+    fn try_from(config: ThemeConfig) -> Result<Self, Self::Error> {
+        match config {
+            ThemeConfig::Palette(index) if index < PALETTES.len() => Ok(Theme::Palette(index)),
+            ThemeConfig::Palette(index) => Err(format!(
+                "Theme {index} does not exist, pick one from 0 to {}",
+                PALETTES.len() - 1
+            )),
+            ThemeConfig::Custom(mut colors) => {
+                let mut color = |name: &str| {
+                    colors
+                        .remove(name)
+                        .ok_or_else(|| format!("Missing theme color \"{name}\""))
+                        .and_then(|value| parse_color(&value))
+                };
+                let theme = TableColors {
+                    buffer_bg: color("buffer_bg")?,
+                    header_bg: color("header_bg")?,
+                    header_fg: color("header_fg")?,
+                    row_fg: color("row_fg")?,
+                    selected_style_fg: color("selected_style_fg")?,
+                    normal_row_color: color("normal_row_color")?,
+                    alt_row_color: color("alt_row_color")?,
+                };
+                match colors.keys().next() {
+                    Some(name) => Err(format!("Unknown theme color \"{name}\"")),
+                    None => Ok(Theme::Custom(theme)),
+                }
+            }
+        }
+    }
+}
+
+impl From<&Theme> for TableColors {
+    fn from(theme: &Theme) -> Self {
+        match theme {
+            Theme::Palette(index) => TableColors::new(&PALETTES[*index]),
+            Theme::Custom(colors) => *colors,
+        }
+    }
+}
+
+// Placeholders alert_template may use, each filled in from the event being alerted for.
+const ALERT_PLACEHOLDERS: [&str; 9] = [
+    "subject",
+    "organizer",
+    "location",
+    "when",
+    "start",
+    "end",
+    "duration",
+    "minutes",
+    "calendar",
+];
+
+// Text of alerts, in the popup and desktop notifications, e.g. "{subject} in {minutes}m". Checked
+// for unknown placeholders when the config loads.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct AlertTemplate(String);
+
+impl Default for AlertTemplate {
+    fn default() -> Self {
+        AlertTemplate("{subject}\n{when}\n{organizer}".to_string())
+    }
+}
+
+impl TryFrom<String> for AlertTemplate {
+    type Error = String;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unclosed {{ in alert_template \"{template}\""))?;
+            let name = &rest[start + 1..start + end];
+            if !ALERT_PLACEHOLDERS.contains(&name) {
+                return Err(format!(
+                    "Unknown placeholder {{{name}}} in alert_template, use one of {}",
+                    ALERT_PLACEHOLDERS
+                        .map(|name| format!("{{{name}}}"))
+                        .join(", ")
+                ));
+            }
+            rest = &rest[start + end + 1..];
+        }
+        Ok(AlertTemplate(template))
+    }
+}
+
+impl AlertTemplate {
+    // In one pass, so braces inside the event's own text are left alone.
+    pub fn render(&self, event: &CalendarEvent) -> String {
+        let mut text = String::with_capacity(self.0.len());
+        let mut rest = self.0.as_str();
+        while let Some((start, end)) = rest
+            .find('{')
+            .and_then(|start| Some((start, start + rest[start..].find('}')?)))
+        {
+            text.push_str(&rest[..start]);
+            text.push_str(&placeholder(&rest[start + 1..end], event));
+            rest = &rest[end + 1..];
+        }
+        text.push_str(rest);
+        text
+    }
+}
+
+fn placeholder(name: &str, event: &CalendarEvent) -> String {
+    match name {
+        "subject" => event.subject.clone(),
+        "organizer" => event.organizer.clone(),
+        "location" => event.location.clone(),
+        "when" => format_span(event),
+        "start" => format_time(&to_display(event.start_time)),
+        "end" => format_time(&to_display(event.end_time)),
+        "duration" => format_duration(event),
+        "minutes" => (event.start_time - Utc::now())
+            .num_minutes()
+            .max(0)
+            .to_string(),
+        "calendar" => event.source().unwrap_or_default().to_string(),
+        _ => String::new(),
+    }
+}
+
+// [category_colors] maps category names to colors, matched ignoring case.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(try_from = "HashMap<String, String>")]
+pub struct CategoryColors(HashMap<String, Color>);
+
+impl TryFrom<HashMap<String, String>> for CategoryColors {
+    type Error = String;
+
+    fn try_from(config: HashMap<String, String>) -> Result<Self, Self::Error> {
+        config
+            .into_iter()
+            .map(|(category, value)| Ok((category.to_lowercase(), parse_color(&value)?)))
+            .collect::<Result<_, _>>()
+            .map(CategoryColors)
+    }
+}
+
+impl CategoryColors {
+    // The color of the first category that has one.
+    pub fn get(&self, categories: &[String]) -> Option<Color> {
+        categories
+            .iter()
+            .find_map(|category| self.0.get(&category.to_lowercase()).copied())
+    }
+}
+
+// Accepts "#rrggbb" as well as ratatui's color names, e.g. "red" or "lightblue".
+pub fn parse_color(value: &str) -> Result<Color, String> {
+    Color::from_str(value).map_err(|_| format!("Unknown color \"{value}\""))
+}
+
+// In display_timezone when set, otherwise the system's zone.
+pub fn to_display(time: DateTime<Utc>) -> DateTime<FixedOffset> {
+    in_zone(time, CONFIG.get().unwrap().display_tz())
+}
+
+fn in_zone(time: DateTime<Utc>, tz: Option<Tz>) -> DateTime<FixedOffset> {
+    match tz {
+        Some(tz) => time.with_timezone(&tz).fixed_offset(),
+        None => time.with_timezone(&Local).fixed_offset(),
+    }
+}
+
+pub fn format_time(dt: &DateTime<FixedOffset>) -> String {
+    match CONFIG.get().unwrap().time_format {
+        TimeFormat::TwelveHour => dt.format("%-I:%M %p").to_string(),
+        TimeFormat::TwentyFourHour => dt.format("%H:%M").to_string(),
+    }
+}
+
+pub fn format_span(event: &CalendarEvent) -> String {
+    let start = to_display(event.start_time);
+    let end = to_display(event.end_time);
+    match event.is_all_day {
+        true => format!("{} (all day)", start.format("%a %-d %b")),
+        false => format!(
+            "{} {} - {}",
+            start.format("%a %-d %b"),
+            format_time(&start),
+            format_time(&end)
+        ),
+    }
+}
+
+// "45m", "2h" or "1h 30m". Zero and negative lengths, which only bad data produces, show as 0m.
+pub fn format_duration(event: &CalendarEvent) -> String {
+    if event.is_all_day {
+        return "All day".to_string();
+    }
+    format_minutes((event.end_time - event.start_time).num_minutes().max(0))
+}
+
+fn format_minutes(minutes: i64) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{minutes}m"),
+        (hours, 0) => format!("{hours}h"),
+        (hours, minutes) => format!("{hours}h {minutes}m"),
+    }
+}
+
+pub fn format_countdown(until: chrono::Duration) -> String {
+    let seconds = until.num_seconds();
+    match seconds {
+        ..=0 => "now".to_string(),
+        1..=59 => format!("in {seconds}s"),
+        60..=3599 => format!("in {}m", seconds / 60),
+        _ => format!("in {}h {:02}m", seconds / 3600, (seconds % 3600) / 60),
+    }
+}
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+// Advances a frame every 100ms, driven by the redraw loop.
+fn spinner(now: DateTime<Utc>) -> char {
+    SPINNER[(now.timestamp_millis() / 100) as usize % SPINNER.len()]
+}
+
+// "! " for high importance and a lock for private events, shown ahead of the subject.
+fn markers(event: &CalendarEvent) -> String {
+    let mut markers = String::new();
+    if event.importance == Importance::High {
+        markers.push_str("! ");
+    }
+    if event.is_private {
+        markers.push_str("🔒 ");
+    }
+    markers
+}
+
+// Solid for busy, hollow for tentative, hatched for out of office.
+fn show_as_glyph(show_as: ShowAs) -> &'static str {
+    match show_as {
+        ShowAs::Busy => "█ ",
+        ShowAs::Tentative => "▯ ",
+        ShowAs::OutOfOffice => "▨ ",
+        ShowAs::WorkingElsewhere => "▤ ",
+        ShowAs::Free => "  ",
+    }
+}
+
+// Cuts text down to width characters, ending in an ellipsis when anything was cut.
+fn truncate(text: &str, width: usize) -> String {
+    match text.chars().count() > width {
+        true => text
+            .chars()
+            .take(width.saturating_sub(1))
+            .chain(std::iter::once('…'))
+            .take(width)
+            .collect(),
+        false => text.to_string(),
+    }
+}
+
+// width is the column's share of the table, which long text is truncated to.
+fn column_cell<'a>(app: &App, e: &'a CalendarEvent, column: Column, width: usize) -> Cell<'a> {
+    let start = to_display(e.start_time);
+    let end = to_display(e.end_time);
+    match column {
+        Column::Subject => {
+            let subject = match &e.recurrence {
+                Some(recurrence) => format!("{} {recurrence}", e.subject),
+                None => e.subject.clone(),
+            };
+            let mut spans = vec![
+                e.calendar_name.as_deref().map_or(Span::from(""), |name| {
+                    Span::from("▌ ").fg(calendar_color(name))
+                }),
+                Span::from(show_as_glyph(e.show_as)).fg(app.colors.selected_style_fg),
+                CONFIG
+                    .get()
+                    .unwrap()
+                    .category_colors
+                    .get(&e.categories)
+                    .map_or(Span::from(""), |color| Span::from("■ ").fg(color)),
+                Span::styled(
+                    markers(e),
+                    Style::default().fg(app.colors.selected_style_fg).bold(),
+                ),
+            ];
+            let room = width.saturating_sub(spans.iter().map(Span::width).sum());
+            spans.push(Span::from(truncate(&subject, room)));
+            Cell::new(Line::from(spans)).style(Style::default().bold())
+        }
+        Column::Start if !e.is_all_day && e.start_time <= Utc::now() => Cell::new(format!(
+            "● in progress (ends {})",
+            format_countdown(e.end_time - Utc::now())
+        ))
+        .fg(app.colors.selected_style_fg),
+        Column::Start => Cell::new(match e.is_all_day {
+            true => start.format("%Y-%m-%d").to_string(),
+            false => format!("{} @ {}", start.format("%Y-%m-%d"), format_time(&start)),
+        }),
+        Column::End => Cell::new(match e.is_all_day {
+            true => String::new(),
+            false => format_time(&end),
+        }),
+        Column::Duration => Cell::new(format_duration(e)),
+        Column::Location => Cell::new(truncate(&e.location, width)),
+        Column::Organizer => Cell::new(truncate(&e.organizer, width)),
+        Column::Account => Cell::new(e.account.as_deref().map_or(Line::from(""), |name| {
+            Line::from(vec![
+                Span::from("▌ ").fg(calendar_color(name)),
+                Span::from(truncate(name, width.saturating_sub(2))),
+            ])
+        })),
+        Column::Response => Cell::new(
+            e.response
+                .as_ref()
+                .map_or(String::new(), |response| response.to_string()),
+        ),
+    }
+}
+
+// A line of the table, laid out before any cells are built.
+pub enum TableRow {
+    Day(NaiveDate),
+    Event {
+        // Into App::visible_events.
+        index: usize,
+        // Position within its day, for striping.
+        stripe: usize,
+    },
+    OutsideHours(usize),
+}
+
+impl TableRow {
+    fn height(&self) -> u16 {
+        match self {
+            TableRow::Event { .. } => 3,
+            TableRow::Day(_) | TableRow::OutsideHours(_) => 1,
+        }
+    }
+}
+
+// The visible events with day headers between them, and the row each event ended up on.
+#[derive(Default)]
+pub struct TableLayout {
+    pub rows: Vec<TableRow>,
+    pub event_rows: Vec<usize>,
+}
+
+impl TableLayout {
+    pub fn new(events: &[&CalendarEvent], outside_hours: usize) -> Self {
+        let mut layout = TableLayout::default();
+        let mut current_day = None;
+        let mut stripe = 0;
+        // Alphabetical order would split days into a heading per event.
+        let by_day = CONFIG.get().unwrap().sort.by != SortBy::Subject;
+
+        for (index, event) in events.iter().enumerate() {
+            let date = to_display(event.start_time).date_naive();
+            if by_day && current_day != Some(date) {
+                current_day = Some(date);
+                stripe = 0;
+                layout.rows.push(TableRow::Day(date));
+            }
+            layout.event_rows.push(layout.rows.len());
+            layout.rows.push(TableRow::Event { index, stripe });
+            stripe += 1;
+        }
+        if outside_hours > 0 {
+            layout.rows.push(TableRow::OutsideHours(outside_hours));
+        }
+        layout
+    }
+}
+
+// The range of rows to draw: from offset where possible, scrolled just enough to show the
+// selected row, and as many as fit in height.
+fn visible_window(
+    rows: &[TableRow],
+    offset: usize,
+    selected: Option<usize>,
+    height: u16,
+) -> (usize, usize) {
+    let height_of = |rows: &[TableRow]| rows.iter().map(TableRow::height).sum::<u16>();
+    let mut first = offset.min(rows.len().saturating_sub(1));
+    if let Some(selected) = selected {
+        first = first.min(selected);
+        while first < selected && height_of(&rows[first..=selected]) > height {
+            first += 1;
+        }
+    }
+
+    let mut last = first;
+    let mut used = 0;
+    while last < rows.len() && (last == first || used + rows[last].height() <= height) {
+        used += rows[last].height();
+        last += 1;
+    }
+    (first, last)
+}
+
+// Stable per name, so a calendar keeps its color across refreshes and restarts.
+fn calendar_color(name: &str) -> Color {
+    let hash = name.bytes().fold(0usize, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(byte as usize)
+    });
+    PALETTES[hash % PALETTES.len()].c400
 }
 
 pub fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
@@ -62,16 +544,38 @@ pub fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
 }
 
 pub fn render_popup(app: &mut App, frame: &mut Frame, area: Rect) {
-    let block = Block::default().title("Event").borders(Borders::ALL);
-    let text = app
-        .events
-        .first_key_value()
-        .map_or(Paragraph::new(""), |(_, event)| {
+    let title = match app.change_notice {
+        Some(_) => "Changed",
+        None => "Event",
+    };
+    let block = Block::default().title(title).borders(Borders::ALL);
+    let keybindings = &CONFIG.get().unwrap().keybindings;
+    let hint = |action: Action, label: &str| format!("{}: {label}", keybindings.keys(action));
+    let text = if let Some(notice) = &app.change_notice {
+        Paragraph::new(Text::styled(
+            format!("{notice}\n\n{}", hint(Action::Dismiss, "dismiss")),
+            Style::default().fg(Color::Yellow).bold(),
+        ))
+    } else {
+        app.alert_event().map_or(Paragraph::new(""), |event| {
+            // Only offer joining when there is something to join.
+            let actions = [
+                event.meeting_url().map(|_| hint(Action::OpenLink, "join")),
+                Some(hint(Action::Snooze, "snooze")),
+                Some(hint(Action::Dismiss, "dismiss")),
+            ];
             Paragraph::new(Text::styled(
-                format!("{}\n{}", event.subject, event.organizer,),
+                format!(
+                    "{}\nStarts {}\n\n{}",
+                    CONFIG.get().unwrap().alert_template.render(event),
+                    // Counted down live, rather than frozen at when the alert fired.
+                    format_countdown(event.start_time - Utc::now()),
+                    actions.into_iter().flatten().collect::<Vec<_>>().join("  "),
+                ),
                 Style::default().fg(Color::Red).bold(),
             ))
-        });
+        })
+    };
 
     let inner_area = centered_rect(60, 20, area);
     frame.render_widget(Clear, area); //this clears out the background
@@ -79,32 +583,117 @@ pub fn render_popup(app: &mut App, frame: &mut Frame, area: Rect) {
     frame.render_widget(text.block(block).on_black(), inner_area);
 }
 
+pub fn render_error(app: &mut App, frame: &mut Frame, area: Rect) {
+    let (title, heading) = match app.auth_error {
+        Some(AuthError::Browser) => ("Sign in", "Opening browser for sign-in..."),
+        Some(AuthError::RequestingDeviceCode | AuthError::DeviceCode(_)) => {
+            ("Sign in", "Waiting for sign-in...")
+        }
+        _ => ("Error", "Unsuccessful authentication!"),
+    };
+    let block = Block::default().title(title).borders(Borders::ALL);
+    let reason = app
+        .auth_error
+        .as_ref()
+        .map_or("Unknown error".to_string(), |error| error.to_string());
+    let text = Paragraph::new(Text::styled(
+        format!(
+            "{heading}\n{reason}\n\nretry: {} | quit: {}",
+            CONFIG.get().unwrap().keybindings.keys(Action::Retry),
+            CONFIG.get().unwrap().keybindings.keys(Action::Quit),
+        ),
+        Style::default().fg(Color::Red).bold(),
+    ))
+    .alignment(Alignment::Center);
+
+    let inner_area = centered_rect(60, 20, area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(text.block(block).on_black(), inner_area);
+}
+
+pub fn render_help(_app: &mut App, frame: &mut Frame, area: Rect) {
+    let keybindings = &CONFIG.get().unwrap().keybindings;
+    let lines = Action::ALL
+        .iter()
+        .map(|action| {
+            Line::from(vec![
+                Span::from(format!("{:<16}", action.name().replace('_', " "))).bold(),
+                Span::from(keybindings.keys(*action)),
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    let block = Block::default()
+        .title(format!(
+            "Keybindings ({} to close)",
+            keybindings.keys(Action::Help)
+        ))
+        .borders(Borders::ALL);
+    let inner_area = centered_rect(40, 70, area);
+    frame.render_widget(Clear, inner_area);
+    frame.render_widget(Paragraph::new(lines).block(block).on_black(), inner_area);
+}
+
+pub fn render_too_small(frame: &mut Frame, area: Rect) {
+    let config = CONFIG.get().unwrap();
+    frame.render_widget(
+        Paragraph::new(format!(
+            "Terminal too small — resize to at least {}x{}",
+            config.min_width, config.min_height
+        ))
+        .wrap(Wrap { trim: true }),
+        area,
+    );
+}
+
+pub fn render_confirm_quit(_app: &mut App, frame: &mut Frame, area: Rect) {
+    let block = Block::default().title("Quit").borders(Borders::ALL);
+    let inner_area = centered_rect(20, 15, area);
+    frame.render_widget(Clear, inner_area);
+    frame.render_widget(
+        Paragraph::new("Quit? (y/n)")
+            .alignment(Alignment::Center)
+            .block(block)
+            .on_black(),
+        inner_area,
+    );
+}
+
 pub fn render_selection(app: &mut App, frame: &mut Frame, area: Rect) {
-    if let Some(i) = app.table_state.selected() {
-        let text = app
-            .events
-            .iter()
-            .nth(i)
-            .map_or(Paragraph::new(""), |(_, event)| {
-                Paragraph::new(Text::styled(
-                    format!(
-                        "{}\n{}\n{}\n{}\n{}\n{}",
-                        event.subject,
-                        event.location,
-                        event.organizer,
-                        event
-                            .teams_meeting
-                            .clone()
-                            .map_or("".to_string(), |meeting| meeting.url),
-                        event
-                            .response
-                            .clone()
-                            .unwrap_or(EventResponse::NotResponded),
-                        event.body
-                    ),
-                    Style::default().fg(Color::Red).bold(),
-                ))
-            });
+    if app.table_state.selected().is_some() {
+        let content = app.selected_event().map_or(String::new(), |event| {
+            format!(
+                "{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}{}",
+                event.subject,
+                match event.is_all_day {
+                    true => format_span(event),
+                    false => format!("{} ({})", format_span(event), format_duration(event)),
+                },
+                event.location,
+                event.organizer,
+                event.categories.join(", "),
+                match event.also_in.is_empty() {
+                    true => String::new(),
+                    false => format!("Also in {}", event.also_in.join(", ")),
+                },
+                event
+                    .teams_meeting
+                    .as_ref()
+                    .map_or(String::new(), |meeting| match meeting.source {
+                        LinkSource::Body => format!("{} (from the description)", meeting.url),
+                        LinkSource::JoinUrl | LinkSource::OnlineMeetingUrl => meeting.url.clone(),
+                    }),
+                event
+                    .response
+                    .as_ref()
+                    .map_or(String::new(), |response| response.to_string()),
+                format_attendees(&event.attendees),
+                match (app.show_raw_body, &event.raw_body) {
+                    (true, Some(raw_body)) => raw_body,
+                    _ => &event.body,
+                }
+            )
+        });
 
         let block = Block::default()
             .title("Event")
@@ -121,7 +710,32 @@ pub fn render_selection(app: &mut App, frame: &mut Frame, area: Rect) {
             .constraints(vec![Constraint::Percentage(70), Constraint::Percentage(30)])
             .split(inner_area);
 
-        let text2 = Paragraph::new(Text::raw("\nACCEPT | REJECT")).alignment(Alignment::Center);
+        // Keep the last line of the body reachable but no further.
+        let text_area = block.inner(layout[0]);
+        let height = wrapped_height(&content, text_area.width);
+        let max_scroll = height.saturating_sub(text_area.height);
+        app.detail_scroll = app.detail_scroll.min(max_scroll);
+
+        let text = Paragraph::new(Text::styled(
+            content,
+            Style::default().fg(Color::Red).bold(),
+        ))
+        .wrap(Wrap { trim: false })
+        .scroll((app.detail_scroll, 0));
+
+        let keybindings = &CONFIG.get().unwrap().keybindings;
+        let text2 = Paragraph::new(Text::raw(format!(
+            "\nACCEPT ({}) | REJECT ({}) | OPEN ({}) | COPY ({}) | SCROLL ({}/{}) | RAW ({})\n{}",
+            keybindings.keys(Action::Accept),
+            keybindings.keys(Action::Decline),
+            keybindings.keys(Action::OpenLink),
+            keybindings.keys(Action::CopyLink),
+            keybindings.keys(Action::Next),
+            keybindings.keys(Action::Prev),
+            keybindings.keys(Action::RawBody),
+            app.status.clone().unwrap_or_default()
+        )))
+        .alignment(Alignment::Center);
         frame.render_widget(Clear, area);
         frame.render_widget(Block::default().bg(Color::Rgb(64, 188, 252)), area);
         frame.render_widget(text.block(block), layout[0]);
@@ -129,70 +743,693 @@ pub fn render_selection(app: &mut App, frame: &mut Frame, area: Rect) {
     }
 }
 
+// Attendees listed above the body.
+const MAX_ATTENDEES: usize = 10;
+
+// A summary such as "5 accepted, 1 no response" followed by each attendee, ending in a blank line.
+fn format_attendees(attendees: &[EventAttendee]) -> String {
+    if attendees.is_empty() {
+        return String::new();
+    }
+
+    let summary = [
+        AttendeeResponse::Accepted,
+        AttendeeResponse::Tentative,
+        AttendeeResponse::Declined,
+        AttendeeResponse::NoResponse,
+    ]
+    .into_iter()
+    .filter_map(|response| {
+        let count = attendees
+            .iter()
+            .filter(|attendee| attendee.response == response)
+            .count();
+        (count > 0).then(|| format!("{count} {response}"))
+    })
+    .collect::<Vec<_>>()
+    .join(", ");
+
+    let mut lines = vec![summary];
+    lines.extend(
+        attendees
+            .iter()
+            .take(MAX_ATTENDEES)
+            .map(|attendee| format!("  {} ({})", attendee.name, attendee.response)),
+    );
+    if attendees.len() > MAX_ATTENDEES {
+        lines.push(format!("  +{} more", attendees.len() - MAX_ATTENDEES));
+    }
+    format!("{}\n\n", lines.join("\n"))
+}
+
+// Approximates the rows a wrapped paragraph occupies, counting characters per line.
+fn wrapped_height(text: &str, width: u16) -> u16 {
+    let width = width.max(1) as usize;
+    text.lines()
+        .map(|line| line.chars().count().max(1).div_ceil(width))
+        .sum::<usize>()
+        .min(u16::MAX as usize) as u16
+}
+
 pub fn render_table(app: &mut App, frame: &mut Frame, area: Rect) {
-    let layout = Layout::horizontal([Constraint::Percentage(100)])
-        .flex(layout::Flex::SpaceBetween)
-        .split(area);
+    let layout = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .split(area);
 
     let header_style = Style::default()
         .fg(app.colors.header_fg)
         .bg(app.colors.header_bg);
-    let selected_style = Style::default()
-        .add_modifier(Modifier::REVERSED)
-        .fg(app.colors.selected_style_fg);
-    let header = [
-        Text::from("Event")
-            .style(Style::default().bold())
-            .alignment(Alignment::Left),
-        Text::from("Start Time")
-            .style(Style::default().bold())
-            .alignment(Alignment::Left),
-        Text::from("Duration")
-            .style(Style::default().bold())
-            .alignment(Alignment::Left),
-    ]
-    .iter()
-    .cloned()
-    .map(Cell::from)
-    .collect::<Row>()
-    .style(header_style)
-    .height(2);
-
-    let footer = Row::new(vec![Cell::from("up/down: k/j | open/close: l/h").bold()])
-        .height(1)
-        .top_margin(0);
-
-    let rows = app.events.iter().enumerate().map(|(i, (_, e))| {
-        let color = match i % 2 {
-            0 => app.colors.normal_row_color,
-            _ => app.colors.alt_row_color,
-        };
+    // A fill is invisible without colors.
+    let selected_style = match (
+        CONFIG.get().unwrap().no_color(),
+        CONFIG.get().unwrap().selection_style,
+    ) {
+        (true, SelectionStyle::Bg) => SelectionStyle::Reversed,
+        (_, selection_style) => selection_style,
+    }
+    .style(&app.colors);
+    let columns = &CONFIG.get().unwrap().columns;
+    let header = columns
+        .iter()
+        .map(|column| {
+            Cell::from(
+                Text::from(column.title())
+                    .style(Style::default().bold())
+                    .alignment(Alignment::Left),
+            )
+        })
+        .collect::<Row>()
+        .style(header_style)
+        .height(2);
+
+    let keybindings = &CONFIG.get().unwrap().keybindings;
+    let hints = match (app.filtering, app.filter.is_empty()) {
+        (true, _) => format!("/{}", app.filter),
+        (false, false) => format!(
+            "filter: {} ({} to clear)",
+            app.filter,
+            keybindings.keys(Action::ClearFilter)
+        ),
+        (false, true) => format!(
+            "up/down: {}/{} | open/close: {}/{} | now: {} | filter: {} | week: {} | help: {}",
+            keybindings.keys(Action::Prev),
+            keybindings.keys(Action::Next),
+            keybindings.keys(Action::FocusSelected),
+            keybindings.keys(Action::FocusTable),
+            keybindings.keys(Action::JumpToNow),
+            keybindings.keys(Action::Filter),
+            keybindings.keys(Action::WeekView),
+            keybindings.keys(Action::Help),
+        ),
+    };
+    // Redrawn every poll of the event loop, so this ticks on its own.
+    let now = Utc::now();
+    let countdown = match &app.app_status {
+        AppStatus::Ready => app
+            .events
+            .values()
+            .filter(|event| event.start_time > now)
+            .min_by_key(|event| event.start_time)
+            .map_or("no upcoming events".to_string(), |event| {
+                format!("next {}", format_countdown(event.start_time - now))
+            }),
+        AppStatus::Authenticating => format!("{} Signing in...", spinner(now)),
+        AppStatus::Fetching => format!("{} Fetching events...", spinner(now)),
+        AppStatus::Error(error) => format!("{} {error}", spinner(now)),
+    };
+    // Its own line rather than a table footer, so it doesn't depend on which columns are shown.
+    // Hints are cut first; the status and countdown get the room they need.
+    let status = app.status.clone().unwrap_or_default();
+    let footer = Layout::horizontal([
+        Constraint::Min(0),
+        Constraint::Length(status.chars().count() as u16 + 1),
+        Constraint::Length(countdown.chars().count() as u16),
+    ])
+    .split(layout[2]);
+    frame.render_widget(Block::default().bg(app.colors.buffer_bg), layout[2]);
+    frame.render_widget(Paragraph::new(hints).bold(), footer[0]);
+    frame.render_widget(Paragraph::new(status).fg(Color::Red), footer[1]);
+    frame.render_widget(Paragraph::new(countdown).bold(), footer[2]);
+
+    // Split the whole width by weight, so columns scale with the terminal. Cells are truncated
+    // to the same shares, less the space between columns.
+    let total = columns.iter().map(Column::weight).sum::<u32>();
+    let widths = columns
+        .iter()
+        .map(|column| Constraint::Ratio(column.weight(), total))
+        .collect::<Vec<_>>();
+    let spare = layout[1].width.saturating_sub(columns.len() as u16 - 1) as u32;
+    let column_widths = columns
+        .iter()
+        .map(|column| (spare * column.weight() / total) as usize)
+        .collect::<Vec<_>>();
+
+    // Day header rows are interleaved with events, so the selected event's row index shifts by
+    // the number of headers above it. The layout is kept until the listing changes; cells are
+    // built for the rows on screen.
+    let table_layout = app.table_layout();
+    let selected_row = app
+        .table_state
+        .selected()
+        .and_then(|index| table_layout.event_rows.get(index).copied());
+
+    // Less the header rows.
+    let height = layout[1].height.saturating_sub(2);
+    let (first, last) = visible_window(
+        &table_layout.rows,
+        app.table_rows.start,
+        selected_row,
+        height,
+    );
 
-        let duration = &e.end_time.signed_duration_since(e.start_time).num_minutes();
-        let subject = e.subject.clone();
-        let local_dt: DateTime<Local> = DateTime::from(e.start_time);
-        let date = local_dt.date_naive();
-        let time = local_dt.time();
-
-        Row::new(vec![
-            Cell::new(Span::from(subject)).style(Style::default().bold()),
-            Cell::new(Span::from(format!("{date:?} @ {time:?}"))),
-            Cell::new(Span::from(format!("{duration:?} mins"))),
-        ])
-        .style(Style::new().fg(app.colors.row_fg).bg(color))
-        .height(3)
+    let today = to_display(Utc::now()).date_naive();
+    let working_hours = CONFIG.get().unwrap().working_hours;
+    let rows = table_layout.rows[first..last].iter().map(|row| match row {
+        TableRow::Day(date) => {
+            let relative = match (*date - today).num_days() {
+                0 => " — Today",
+                1 => " — Tomorrow",
+                _ => "",
+            };
+            Row::new(vec![Cell::new(Span::from(format!(
+                "{}{relative}",
+                date.format("%a %-d %b")
+            )))])
+            .style(header_style.bold())
+        }
+        TableRow::Event { index, stripe } => {
+            let event = app.visible_event(*index).unwrap();
+            // Only reached while they're shown rather than collapsed.
+            let style = match working_hours.is_some_and(|hours| hours.excludes(event)) {
+                true => Style::new().dim(),
+                false => Style::new(),
+            };
+            columns
+                .iter()
+                .zip(&column_widths)
+                .map(|(column, width)| column_cell(app, event, *column, *width))
+                .collect::<Row>()
+                .style(
+                    style
+                        .fg(app.colors.row_fg)
+                        .bg(app.colors.row_color(*stripe)),
+                )
+                .height(3)
+        }
+        TableRow::OutsideHours(count) => Row::new(vec![Cell::new(format!(
+            "{count} event{} outside working hours ({} to show)",
+            if *count == 1 { "" } else { "s" },
+            keybindings.keys(Action::WorkingHours)
+        ))])
+        .style(Style::new().fg(app.colors.row_fg).italic().dim()),
     });
 
-    let widths = [
-        Constraint::Percentage(40),
-        Constraint::Percentage(45),
-        Constraint::Percentage(15),
-    ];
     let table = Table::new(rows, widths)
         .header(header)
-        .footer(footer)
         .bg(app.colors.buffer_bg)
         .highlight_style(selected_style);
 
-    frame.render_stateful_widget(table, layout[0], &mut app.table_state);
+    // The rows are already cut to the window, so render them from the top.
+    let mut state = TableState::default().with_selected(selected_row.map(|row| row - first));
+    frame.render_stateful_widget(table, layout[1], &mut state);
+    app.table_rows = first..last;
+
+    // Redrawn every poll like the countdown, so it keeps time without a timer of its own.
+    let now = to_display(Utc::now());
+    let clock = Paragraph::new(format!(
+        "{} {} ",
+        now.format("%a %-d %b"),
+        format_time(&now)
+    ))
+    .alignment(Alignment::Right)
+    .style(
+        Style::default()
+            .fg(app.colors.header_fg)
+            .bg(app.colors.header_bg)
+            .bold(),
+    );
+    frame.render_widget(clock, layout[0]);
+}
+
+pub fn render_week(app: &mut App, frame: &mut Frame, area: Rect) {
+    let today = to_display(Utc::now()).date_naive();
+    let monday = today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64)
+        + chrono::Duration::weeks(app.week_offset);
+
+    let layout = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .split(area);
+    frame.render_widget(Block::default().bg(app.colors.buffer_bg), area);
+
+    let sunday = monday + chrono::Duration::days(6);
+    let title = Paragraph::new(format!(
+        "Week of {} - {}",
+        monday.format("%a %-d %b"),
+        sunday.format("%a %-d %b")
+    ))
+    .style(
+        Style::default()
+            .fg(app.colors.header_fg)
+            .bg(app.colors.header_bg)
+            .bold(),
+    )
+    .alignment(Alignment::Center);
+    frame.render_widget(title, layout[0]);
+
+    let columns = Layout::horizontal([Constraint::Ratio(1, 7); 7]).split(layout[1]);
+    for (offset, column) in columns.iter().enumerate() {
+        let date = monday + chrono::Duration::days(offset as i64);
+        let title_style = match date == today {
+            true => Style::default().fg(app.colors.selected_style_fg).bold(),
+            false => Style::default().fg(app.colors.header_fg),
+        };
+        let block = Block::default()
+            .title(Span::styled(date.format("%a %-d").to_string(), title_style))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(app.colors.header_bg));
+        let inner = block.inner(*column);
+        frame.render_widget(block, *column);
+
+        let events = app
+            .events_by_start()
+            .into_iter()
+            .filter(|event| to_display(event.start_time).date_naive() == date)
+            .collect::<Vec<_>>();
+
+        // Roughly one line per half hour, so longer meetings stand out.
+        let mut constraints = events
+            .iter()
+            .map(|event| {
+                let minutes = event
+                    .end_time
+                    .signed_duration_since(event.start_time)
+                    .num_minutes();
+                match event.is_all_day {
+                    true => Constraint::Length(2),
+                    false => Constraint::Length((minutes / 30).clamp(2, 8) as u16),
+                }
+            })
+            .collect::<Vec<_>>();
+        constraints.push(Constraint::Min(0));
+        let blocks = Layout::vertical(constraints).split(inner);
+
+        for (i, (event, area)) in events.iter().zip(blocks.iter()).enumerate() {
+            let color = match i % 2 {
+                0 => app.colors.alt_row_color,
+                _ => app.colors.normal_row_color,
+            };
+            let start = to_display(event.start_time);
+            let time = match event.is_all_day {
+                true => "All day".to_string(),
+                false => format_time(&start),
+            };
+            let text = Paragraph::new(format!("{time}\n{}", event.subject))
+                .style(Style::default().fg(app.colors.row_fg).bg(color))
+                .wrap(Wrap { trim: true });
+            frame.render_widget(text, *area);
+        }
+    }
+
+    let keybindings = &CONFIG.get().unwrap().keybindings;
+    let hints = Paragraph::new(format!(
+        "prev/next week: {}/{} | table: {}",
+        keybindings.keys(Action::PrevWeek),
+        keybindings.keys(Action::NextWeek),
+        keybindings.keys(Action::WeekView),
+    ))
+    .style(Style::default().fg(app.colors.row_fg).bold());
+    frame.render_widget(hints, layout[2]);
+}
+
+// Today from the start to the end of [working_hours] (or midnight to midnight) as one bar, one
+// column per slice of time, so gaps between meetings are easy to spot.
+pub fn render_free_busy(app: &mut App, frame: &mut Frame, area: Rect) {
+    let config = CONFIG.get().unwrap();
+    let now = Utc::now();
+    let local_now = to_display(now);
+    let today = local_now.date_naive();
+    let at = |date: NaiveDate, time: NaiveTime| {
+        date.and_time(time)
+            .and_local_timezone(*local_now.offset())
+            .unwrap()
+            .with_timezone(&Utc)
+    };
+    let (from, to) = match &config.working_hours {
+        Some(hours) => (at(today, hours.start), at(today, hours.end)),
+        None => (
+            at(today, NaiveTime::MIN),
+            at(today.succ_opt().unwrap_or(today), NaiveTime::MIN),
+        ),
+    };
+
+    // Events marked free or working elsewhere don't take up the time. The gap search below relies
+    // on them being in start order.
+    let busy = app
+        .events_by_start()
+        .into_iter()
+        .filter(|event| !matches!(event.show_as, ShowAs::Free | ShowAs::WorkingElsewhere))
+        .filter(|event| event.start_time < to && event.end_time > from)
+        .collect::<Vec<_>>();
+
+    // Only what's left of the day counts as open.
+    let mut gaps = Vec::new();
+    let mut cursor = from.max(now);
+    for event in &busy {
+        if event.start_time > cursor {
+            gaps.push((cursor, event.start_time.min(to)));
+        }
+        cursor = cursor.max(event.end_time);
+    }
+    if cursor < to {
+        gaps.push((cursor, to));
+    }
+    let min_gap = chrono::Duration::minutes(config.free_slot_minutes as i64);
+    gaps.retain(|(start, end)| *end - *start >= min_gap);
+
+    let layout = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(3),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .split(area);
+    frame.render_widget(Block::default().bg(app.colors.buffer_bg), area);
+
+    let title = Paragraph::new(format!("Free/busy {}", local_now.format("%a %-d %b")))
+        .style(
+            Style::default()
+                .fg(app.colors.header_fg)
+                .bg(app.colors.header_bg)
+                .bold(),
+        )
+        .alignment(Alignment::Center);
+    frame.render_widget(title, layout[0]);
+
+    let width = layout[2].width as i64;
+    let span = (to - from).num_seconds();
+    // Middle of each column, so a meeting fills the columns it mostly covers.
+    let column_time = |column: i64| {
+        from + chrono::Duration::seconds(span * (2 * column + 1) / (2 * width).max(1))
+    };
+    let bar = (0..width)
+        .map(|column| {
+            let time = column_time(column);
+            let show_as = busy
+                .iter()
+                .filter(|event| event.start_time <= time && time < event.end_time)
+                .map(|event| event.show_as)
+                .max_by_key(|show_as| match show_as {
+                    ShowAs::OutOfOffice => 2,
+                    ShowAs::Busy => 1,
+                    _ => 0,
+                });
+            let open = gaps
+                .iter()
+                .any(|(start, end)| *start <= time && time < *end);
+            match (show_as, open) {
+                (Some(show_as), _) => Span::styled(
+                    show_as_glyph(show_as).trim_end().to_string(),
+                    Style::default()
+                        .fg(app.colors.row_fg)
+                        .bg(app.colors.normal_row_color),
+                ),
+                (None, true) => match config.no_color() {
+                    true => Span::styled(" ", Style::default().reversed()),
+                    false => Span::styled(" ", Style::default().bg(Color::Green)),
+                },
+                (None, false) if time < now => Span::styled(
+                    "·",
+                    Style::default()
+                        .fg(app.colors.row_fg)
+                        .bg(app.colors.buffer_bg)
+                        .dim(),
+                ),
+                (None, false) => Span::styled(" ", Style::default().bg(app.colors.alt_row_color)),
+            }
+        })
+        .collect::<Vec<_>>();
+    let bar = Line::from(bar);
+    frame.render_widget(
+        Paragraph::new(vec![bar.clone(), bar.clone(), bar]),
+        layout[2],
+    );
+
+    // Hour labels under the bar, skipping any that would run into the previous one.
+    let mut ruler = vec![' '; width.max(0) as usize];
+    let mut hour = at(today, NaiveTime::MIN);
+    let mut next_free = 0;
+    while hour < to {
+        if hour >= from {
+            let column = ((hour - from).num_seconds() * width / span.max(1)) as usize;
+            let label = match config.time_format {
+                TimeFormat::TwelveHour => to_display(hour).format("%-I%P").to_string(),
+                TimeFormat::TwentyFourHour => to_display(hour).format("%H").to_string(),
+            };
+            if column >= next_free && column + label.len() <= ruler.len() {
+                for (i, c) in label.chars().enumerate() {
+                    ruler[column + i] = c;
+                }
+                next_free = column + label.len() + 1;
+            }
+        }
+        hour += chrono::Duration::hours(1);
+    }
+    frame.render_widget(
+        Paragraph::new(ruler.into_iter().collect::<String>())
+            .style(Style::default().fg(app.colors.row_fg)),
+        layout[3],
+    );
+
+    let slots = match gaps.is_empty() {
+        true => vec![Line::from("No open slots left today")],
+        false => gaps
+            .iter()
+            .map(|(start, end)| {
+                Line::from(format!(
+                    "Free {} - {} ({})",
+                    format_time(&to_display(*start)),
+                    format_time(&to_display(*end)),
+                    format_minutes((*end - *start).num_minutes()),
+                ))
+            })
+            .collect(),
+    };
+    frame.render_widget(
+        Paragraph::new(slots).style(Style::default().fg(app.colors.row_fg)),
+        layout[5],
+    );
+
+    let keybindings = &config.keybindings;
+    let hints = Paragraph::new(format!(
+        "{}busy {}tentative {}out of office | table: {}",
+        show_as_glyph(ShowAs::Busy),
+        show_as_glyph(ShowAs::Tentative),
+        show_as_glyph(ShowAs::OutOfOffice),
+        keybindings.keys(Action::FreeBusy),
+    ))
+    .style(Style::default().fg(app.colors.row_fg).bold());
+    frame.render_widget(hints, layout[6]);
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use ratatui::{backend::TestBackend, buffer::Buffer};
+
+    use super::*;
+
+    fn draw(app: &mut App, render: fn(&mut App, &mut Frame, Rect)) -> Buffer {
+        let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+        terminal
+            .draw(|frame| render(app, frame, frame.size()))
+            .unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    fn lines(buffer: &Buffer) -> Vec<String> {
+        (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer.get(x, y).symbol())
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    // The canned events, on a fixed day so the headers and times don't move.
+    fn app_with_events() -> App {
+        let mut app = crate::app::tests::app();
+        let anchor = Utc.with_ymd_and_hms(2024, 3, 4, 8, 0, 0).unwrap();
+        for event in crate::mock::events(anchor) {
+            app.events.insert(event.id.clone(), event);
+        }
+        app
+    }
+
+    #[test]
+    fn table_lists_events_under_day_headers() {
+        let mut app = app_with_events();
+        let lines = lines(&draw(&mut app, render_table));
+
+        let header = lines.iter().find(|line| line.starts_with("Event")).unwrap();
+        assert!(header.contains("Start Time") && header.contains("Duration"));
+
+        let expected = [
+            "Mon 4 Mar",
+            "Daily stand-up",
+            "Design review",
+            "1:1 with Sam",
+            "Customer call",
+            "Focus time",
+            "Tue 5 Mar",
+            "Daily stand-up",
+            "Quarterly planning",
+            "Interview debrief",
+        ];
+        let mut rows = lines.iter();
+        for text in expected {
+            assert!(rows.any(|line| line.contains(text)), "{text} missing");
+        }
+        let planning = lines
+            .iter()
+            .find(|line| line.contains("Quarterly planning"))
+            .unwrap();
+        assert!(planning.contains("1h 30m"));
+    }
+
+    #[test]
+    fn table_highlights_the_selected_row() {
+        let mut app = app_with_events();
+        app.table_state.select(Some(1));
+        let buffer = draw(&mut app, render_table);
+        let row = |subject: &str| {
+            lines(&buffer)
+                .iter()
+                .position(|line| line.contains(subject))
+                .unwrap() as u16
+        };
+
+        let reversed = |y| buffer.get(2, y).modifier.contains(Modifier::REVERSED);
+        assert!(reversed(row("Design review")));
+        assert!(!reversed(row("Daily stand-up")));
+    }
+
+    #[test]
+    fn table_without_events_keeps_its_header() {
+        let mut app = crate::app::tests::app();
+        let lines = lines(&draw(&mut app, render_table));
+
+        let header = lines.iter().position(|line| line.starts_with("Event"));
+        let footer = lines.iter().position(|line| line.starts_with("up/down"));
+        let (Some(header), Some(footer)) = (header, footer) else {
+            panic!("header or footer missing");
+        };
+        assert!(lines[header + 1..footer]
+            .iter()
+            .all(|line| line.trim().is_empty()));
+    }
+
+    #[test]
+    fn footer_keeps_the_status_and_countdown() {
+        let mut app = app_with_events();
+        app.app_status = AppStatus::Ready;
+        app.status = Some("Could not accept: offline".to_string());
+        let lines = lines(&draw(&mut app, render_table));
+
+        let footer = lines.last().unwrap();
+        assert!(footer.starts_with("up/down"));
+        assert!(footer.contains("Could not accept: offline"));
+        assert!(footer.trim_end().ends_with("no upcoming events"));
+    }
+
+    #[test]
+    fn selection_shows_the_selected_event() {
+        let mut app = app_with_events();
+        app.table_state.select(Some(1));
+        let lines = lines(&draw(&mut app, render_selection));
+
+        for text in [
+            "Design review",
+            "Mon 4 Mar 09:30 - 10:30 (1h)",
+            "Room 4.02",
+            "Jordan Lee",
+        ] {
+            assert!(
+                lines.iter().any(|line| line.contains(text)),
+                "{text} missing"
+            );
+        }
+    }
+
+    #[test]
+    fn selection_without_a_selected_event_is_blank() {
+        let mut app = crate::app::tests::app();
+        app.table_state.select(None);
+        let lines = lines(&draw(&mut app, render_selection));
+
+        assert!(lines.iter().all(|line| line.trim().is_empty()));
+    }
+
+    #[test]
+    fn colors_parse_from_hex_and_names() {
+        assert_eq!(parse_color("#1e293b"), Ok(Color::Rgb(0x1e, 0x29, 0x3b)));
+        assert_eq!(parse_color("red"), Ok(Color::Red));
+        assert_eq!(parse_color("lightblue"), Ok(Color::LightBlue));
+        assert!(parse_color("#12345").is_err());
+        assert!(parse_color("teal-ish").is_err());
+    }
+
+    #[test]
+    fn rows_are_built_for_the_window_only() {
+        // Ten a day for a hundred days, so 1100 rows with the day headers.
+        let mut app = crate::app::tests::app();
+        let anchor = Utc.with_ymd_and_hms(2024, 3, 4, 8, 0, 0).unwrap();
+        let template = crate::mock::events(anchor).remove(0);
+        for i in 0..1000 {
+            let event = CalendarEvent {
+                id: format!("event-{i}"),
+                subject: format!("Meeting {i}"),
+                start_time: anchor
+                    + chrono::Duration::days(i / 10)
+                    + chrono::Duration::minutes(i % 10 * 30),
+                end_time: anchor
+                    + chrono::Duration::days(i / 10)
+                    + chrono::Duration::minutes(i % 10 * 30 + 30),
+                ..template.clone()
+            };
+            app.events.insert(event.id.clone(), event);
+        }
+        assert_eq!(app.table_layout().rows.len(), 1100);
+
+        // 26 lines below the header: the first day's header and eight events of three lines.
+        draw(&mut app, render_table);
+        assert_eq!(app.table_rows, 0..9);
+
+        // Scrolled just far enough to reach a selection at the far end.
+        app.table_state.select(Some(999));
+        let lines = lines(&draw(&mut app, render_table));
+        assert_eq!(app.table_rows.end, 1100);
+        assert!(app.table_rows.len() <= 10);
+        assert!(lines.iter().any(|line| line.contains("Meeting 999")));
+    }
+
+    #[test]
+    fn one_instant_in_two_display_zones() {
+        let instant = Utc.with_ymd_and_hms(2024, 7, 1, 15, 30, 0).unwrap();
+        let new_york = in_zone(instant, "America/New_York".parse().ok());
+        let tokyo = in_zone(instant, "Asia/Tokyo".parse().ok());
+
+        assert_eq!(new_york.to_rfc3339(), "2024-07-01T11:30:00-04:00");
+        assert_eq!(tokyo.to_rfc3339(), "2024-07-02T00:30:00+09:00");
+        assert_eq!(new_york, tokyo);
+    }
 }